@@ -0,0 +1,478 @@
+//! framing of Comet packets, shared between the kernel and the host side of Comet
+//!
+//! a frame on the wire, all integers little endian:
+//!
+//! ```text
+//! magic    [u8; 2]   0xC0 0x4D
+//! kind     u8        see `Kind`
+//! command  u8        what the frame is about
+//! sequence u16       counts up per sender, a reply carries the sequence of its command
+//! length   u16       of the payload
+//! payload  [u8]
+//! crc      u32       CRC-32 (IEEE) of everything between magic and crc
+//! ```
+//!
+//! bytes outside of frames, like plain console text, are skipped by the decoder
+
+#![no_std]
+
+use core::fmt;
+
+/// marks the start of a frame
+pub const MAGIC: [u8; 2] = [0xC0, 0x4D];
+
+/// bytes before the payload, including the magic
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + 1 + 2 + 2;
+
+/// bytes after the payload
+pub const CRC_LEN: usize = 4;
+
+/// longer payloads are rejected by both the encoder and the decoder
+pub const MAX_PAYLOAD_LEN: usize = 4096;
+
+/// size of a frame with the longest payload
+pub const MAX_FRAME_LEN: usize = HEADER_LEN + MAX_PAYLOAD_LEN + CRC_LEN;
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// the channel a frame belongs to
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Kind {
+    /// a log record
+    Log = 0x01,
+
+    /// a request the other side is expected to answer
+    Command = 0x02,
+
+    /// the answer to the command with the same sequence number
+    Reply = 0x03,
+}
+
+/// a decoded frame, or one to be encoded
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Frame<'a> {
+    /// the channel of the frame
+    pub kind: Kind,
+
+    /// what the frame is about, the meaning is up to the protocol on top
+    pub command: u8,
+
+    /// counts up per sender, a reply carries the sequence number of its command
+    pub sequence: u16,
+
+    /// at most `MAX_PAYLOAD_LEN` bytes
+    pub payload: &'a [u8],
+}
+
+/// why a frame could not be encoded
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EncodeError {
+    /// the payload exceeds `MAX_PAYLOAD_LEN`
+    PayloadTooLong(usize),
+
+    /// the buffer can't hold the encoded frame
+    BufferTooSmall {
+        /// the size the buffer needs
+        needed: usize,
+    },
+}
+
+/// why received bytes that started like a frame were skipped
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// the header names a kind that doesn't exist
+    UnknownKind(u8),
+
+    /// the header announces a payload longer than `MAX_PAYLOAD_LEN`
+    PayloadTooLong(usize),
+
+    /// the frame was damaged on the way
+    CrcMismatch {
+        /// the CRC at the end of the frame
+        received: u32,
+
+        /// the CRC of the bytes actually received
+        computed: u32,
+    },
+}
+
+/// collects bytes until they form a complete frame
+pub struct Decoder {
+    /// starts with a possible frame, as far as it has been received
+    buf: [u8; MAX_FRAME_LEN],
+    len: usize,
+
+    /// whether `buf` starts with the frame completed by the last push
+    complete: bool,
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+/// CRC-32 as used by zlib and Ethernet
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+impl Kind {
+    /// the kind with the wire value `value`, if there is one
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x01 => Some(Self::Log),
+            0x02 => Some(Self::Command),
+            0x03 => Some(Self::Reply),
+            _ => None,
+        }
+    }
+}
+
+impl Frame<'_> {
+    /// the number of bytes `encode` writes
+    pub const fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.payload.len() + CRC_LEN
+    }
+
+    /// write the frame to the start of `buf`, returns the number of bytes written
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, EncodeError> {
+        if self.payload.len() > MAX_PAYLOAD_LEN {
+            return Err(EncodeError::PayloadTooLong(self.payload.len()));
+        }
+
+        let len = self.encoded_len();
+        if buf.len() < len {
+            return Err(EncodeError::BufferTooSmall { needed: len });
+        }
+
+        buf[0..2].copy_from_slice(&MAGIC);
+        buf[2] = self.kind as u8;
+        buf[3] = self.command;
+        buf[4..6].copy_from_slice(&self.sequence.to_le_bytes());
+        buf[6..8].copy_from_slice(&(self.payload.len() as u16).to_le_bytes());
+        buf[HEADER_LEN..HEADER_LEN + self.payload.len()].copy_from_slice(self.payload);
+
+        let crc = crc32(&buf[MAGIC.len()..HEADER_LEN + self.payload.len()]);
+        buf[HEADER_LEN + self.payload.len()..len].copy_from_slice(&crc.to_le_bytes());
+
+        Ok(len)
+    }
+}
+
+impl Decoder {
+    /// a decoder waiting for the start of a frame
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_FRAME_LEN],
+            len: 0,
+            complete: false,
+        }
+    }
+
+    fn payload_len(&self) -> usize {
+        u16::from_le_bytes([self.buf[6], self.buf[7]]) as usize
+    }
+
+    fn frame_len(&self) -> usize {
+        HEADER_LEN + self.payload_len() + CRC_LEN
+    }
+
+    fn decoded_frame(&self) -> Frame<'_> {
+        Frame {
+            kind: Kind::from_u8(self.buf[2]).unwrap(),
+            command: self.buf[3],
+            sequence: u16::from_le_bytes([self.buf[4], self.buf[5]]),
+            payload: &self.buf[HEADER_LEN..HEADER_LEN + self.payload_len()],
+        }
+    }
+
+    /// drop the first `count` buffered bytes
+    fn consume(&mut self, count: usize) {
+        self.buf.copy_within(count..self.len, 0);
+        self.len -= count;
+    }
+
+    /// whether the buffer starts like a frame, as far as it goes
+    fn starts_with_magic(&self) -> bool {
+        let len = self.len.min(MAGIC.len());
+
+        self.buf[..len] == MAGIC[..len]
+    }
+
+    /// look at the buffered bytes, dropping what can't be the start of a frame, returns whether
+    /// the buffer starts with a complete frame, or why the frame it started with was dropped
+    fn scan(&mut self) -> Result<bool, DecodeError> {
+        while !self.starts_with_magic() {
+            self.consume(1);
+        }
+
+        if self.len < HEADER_LEN {
+            return Ok(false);
+        }
+
+        // a broken frame only loses its first byte, a real frame may start within it
+        if Kind::from_u8(self.buf[2]).is_none() {
+            let kind = self.buf[2];
+            self.consume(1);
+
+            return Err(DecodeError::UnknownKind(kind));
+        }
+
+        if self.payload_len() > MAX_PAYLOAD_LEN {
+            let payload_len = self.payload_len();
+            self.consume(1);
+
+            return Err(DecodeError::PayloadTooLong(payload_len));
+        }
+
+        if self.len < self.frame_len() {
+            return Ok(false);
+        }
+
+        let payload_end = HEADER_LEN + self.payload_len();
+        let received = u32::from_le_bytes(self.buf[payload_end..payload_end + CRC_LEN].try_into().unwrap());
+        let computed = crc32(&self.buf[MAGIC.len()..payload_end]);
+
+        if received != computed {
+            self.consume(1);
+
+            return Err(DecodeError::CrcMismatch { received, computed });
+        }
+
+        Ok(true)
+    }
+
+    /// drop a partially received frame
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.complete = false;
+    }
+
+    /// the frame completed by the last call to `push`, if any
+    pub fn frame(&self) -> Option<Frame<'_>> {
+        self.complete.then(|| self.decoded_frame())
+    }
+
+    /// feed the next received byte, returns the frame it completes, if any
+    ///
+    /// a broken frame is reported once and then skipped, the bytes it was made of are searched for
+    /// the start of another frame. errors found on the way are only reported if no frame completes
+    /// with this byte
+    pub fn push(&mut self, byte: u8) -> Option<Result<Frame<'_>, DecodeError>> {
+        if self.complete {
+            self.consume(self.frame_len());
+            self.complete = false;
+        }
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+
+        let mut first_error = None;
+
+        loop {
+            match self.scan() {
+                Ok(true) => {
+                    self.complete = true;
+                    return Some(Ok(self.decoded_frame()));
+                }
+                Ok(false) => return first_error.map(Err),
+                Err(x) => {
+                    first_error.get_or_insert(x);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownKind(kind) => write!(f, "unknown frame kind {:#04x}", kind),
+            Self::PayloadTooLong(len) => write!(f, "payload of {} bytes exceeds {} bytes", len, MAX_PAYLOAD_LEN),
+            Self::CrcMismatch { received, computed } => write!(f, "CRC mismatch, received {:#010x}, computed {:#010x}", received, computed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    /// an owned copy of what the decoder reported for a byte
+    type Decoded = Result<(Kind, u8, u16, Vec<u8>), DecodeError>;
+
+    fn encode(frame: &Frame) -> Vec<u8> {
+        let mut buf = std::vec![0; frame.encoded_len()];
+        assert_eq!(frame.encode(&mut buf), Ok(buf.len()));
+
+        buf
+    }
+
+    /// push all of `bytes`, collecting what the decoder reports
+    fn decode_all(decoder: &mut Decoder, bytes: &[u8]) -> Vec<Decoded> {
+        bytes
+            .iter()
+            .filter_map(|byte| decoder.push(*byte).map(|result| result.map(|frame| (frame.kind, frame.command, frame.sequence, frame.payload.to_vec()))))
+            .collect()
+    }
+
+    const FRAME: Frame = Frame { kind: Kind::Command, command: 0x02, sequence: 0x1234, payload: b"hello" };
+
+    fn expected() -> Decoded {
+        Ok((FRAME.kind, FRAME.command, FRAME.sequence, FRAME.payload.to_vec()))
+    }
+
+    #[test]
+    fn crc32_check_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = encode(&FRAME);
+
+        assert_eq!(bytes.len(), HEADER_LEN + FRAME.payload.len() + CRC_LEN);
+        assert_eq!(bytes[..MAGIC.len()], MAGIC);
+        assert_eq!(decode_all(&mut Decoder::new(), &bytes), [expected()]);
+    }
+
+    #[test]
+    fn round_trip_empty_and_longest_payload() {
+        let payload = [0xA5; MAX_PAYLOAD_LEN];
+
+        for payload in [&[][..], &payload[..]] {
+            let frame = Frame { kind: Kind::Log, command: 0, sequence: 7, payload };
+
+            assert_eq!(decode_all(&mut Decoder::new(), &encode(&frame)), [Ok((Kind::Log, 0, 7, payload.to_vec()))]);
+        }
+    }
+
+    #[test]
+    fn encode_errors() {
+        let payload = [0; MAX_PAYLOAD_LEN + 1];
+        let too_long = Frame { payload: &payload, ..FRAME };
+        assert_eq!(too_long.encode(&mut [0; MAX_FRAME_LEN + 1]), Err(EncodeError::PayloadTooLong(MAX_PAYLOAD_LEN + 1)));
+
+        let mut buf = [0; 8];
+        assert_eq!(FRAME.encode(&mut buf), Err(EncodeError::BufferTooSmall { needed: FRAME.encoded_len() }));
+    }
+
+    #[test]
+    fn resyncs_over_plain_text() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice("boot log, with UTF-8 text: grüße → ½\n".as_bytes());
+        bytes.extend_from_slice(&encode(&FRAME));
+        bytes.extend_from_slice(b"more text\xC0");
+        bytes.extend_from_slice(&encode(&FRAME));
+
+        assert_eq!(decode_all(&mut Decoder::new(), &bytes), [expected(), expected()]);
+    }
+
+    #[test]
+    fn frame_split_across_pushes() {
+        let bytes = encode(&FRAME);
+        let mut decoder = Decoder::new();
+
+        for byte in &bytes[..bytes.len() - 1] {
+            assert!(decoder.push(*byte).is_none());
+            assert!(decoder.frame().is_none());
+        }
+
+        assert_eq!(decoder.push(bytes[bytes.len() - 1]), Some(Ok(FRAME)));
+        assert_eq!(decoder.frame(), Some(FRAME));
+
+        // the frame is gone as soon as the next byte arrives
+        assert!(decoder.push(b'x').is_none());
+        assert!(decoder.frame().is_none());
+    }
+
+    #[test]
+    fn crc_mismatch() {
+        let mut bytes = encode(&FRAME);
+        bytes[HEADER_LEN] ^= 0x01;
+
+        let results = decode_all(&mut Decoder::new(), &bytes);
+
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(DecodeError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn unknown_kind() {
+        let mut bytes = encode(&FRAME);
+        bytes[2] = 0x7F;
+
+        assert_eq!(decode_all(&mut Decoder::new(), &bytes), [Err(DecodeError::UnknownKind(0x7F))]);
+    }
+
+    #[test]
+    fn payload_too_long() {
+        let mut bytes = encode(&FRAME);
+        bytes[6..8].copy_from_slice(&(MAX_PAYLOAD_LEN as u16 + 1).to_le_bytes());
+
+        assert_eq!(decode_all(&mut Decoder::new(), &bytes[..HEADER_LEN]), [Err(DecodeError::PayloadTooLong(MAX_PAYLOAD_LEN + 1))]);
+    }
+
+    #[test]
+    fn frame_after_broken_frame() {
+        let mut bytes = encode(&FRAME);
+        bytes[HEADER_LEN] ^= 0x01;
+        bytes.extend_from_slice(&encode(&FRAME));
+
+        let results = decode_all(&mut Decoder::new(), &bytes);
+
+        assert!(matches!(results[0], Err(DecodeError::CrcMismatch { .. })));
+        assert_eq!(results[1..], [expected()]);
+    }
+
+    #[test]
+    fn frame_inside_broken_header() {
+        // a header announcing a long payload swallows the real frame that follows it, which
+        // must still be found once the CRC fails
+        let mut bytes = Vec::from(MAGIC);
+        bytes.extend_from_slice(&[Kind::Log as u8, 0, 0, 0]);
+        bytes.extend_from_slice(&(FRAME.encoded_len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&encode(&FRAME));
+        bytes.extend_from_slice(&[0; CRC_LEN]);
+
+        let results = decode_all(&mut Decoder::new(), &bytes);
+
+        assert_eq!(results, [expected()]);
+    }
+
+    #[test]
+    fn magic_within_bad_kind() {
+        // the kind byte of a broken header is the start of a real frame
+        let mut bytes = Vec::from(MAGIC);
+        bytes.extend_from_slice(&encode(&FRAME));
+
+        assert_eq!(decode_all(&mut Decoder::new(), &bytes), [Err(DecodeError::UnknownKind(MAGIC[0])), expected()]);
+    }
+}
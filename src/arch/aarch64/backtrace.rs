@@ -0,0 +1,31 @@
+use core::arch::asm;
+
+/// the record every function prologue pushes and points x29 at
+#[repr(C)]
+pub struct FrameRecord {
+    /// the caller's frame pointer
+    pub previous: usize,
+    pub return_address: usize,
+}
+
+/// size of the instruction a return address points behind
+pub const CALL_INSTRUCTION_SIZE: usize = 4;
+
+/// required alignment of a frame record, which lives on the 16 byte aligned stack
+pub const FRAME_RECORD_ALIGN: usize = 16;
+
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe { asm!("mov {}, x29", out(reg) fp, options(nomem, nostack)) };
+
+    fp
+}
+
+#[inline(always)]
+pub fn program_counter() -> usize {
+    let pc: usize;
+    unsafe { asm!("adr {}, .", out(reg) pc, options(nomem, nostack)) };
+
+    pc
+}
@@ -0,0 +1,66 @@
+use core::{arch::{asm, global_asm}, cell::UnsafeCell};
+
+use crate::memory::{Address, Physical};
+
+global_asm!(include_str!("chainload.s"));
+
+// provided by `chainload.s`
+extern "Rust" {
+    static __chainload_trampoline_start: UnsafeCell<()>;
+    static __chainload_trampoline_end_exclusive: UnsafeCell<()>;
+    static __chainload_boot: UnsafeCell<()>;
+    static __chainload_park: UnsafeCell<()>;
+}
+
+/// the position independent code that has to be copied out of the way of the new image, see
+/// `chainload.s`
+pub fn trampoline() -> &'static [u8] {
+    unsafe {
+        let start = __chainload_trampoline_start.get() as *const u8;
+        let size = (__chainload_trampoline_end_exclusive.get() as usize) - (start as usize);
+
+        core::slice::from_raw_parts(start, size)
+    }
+}
+
+fn trampoline_offset(symbol: &UnsafeCell<()>) -> usize {
+    symbol.get() as usize - unsafe { __chainload_trampoline_start.get() } as usize
+}
+
+/// copy the image and jump to it from EL2, once all other cores called `park()`
+///
+/// # safety
+/// - `phys_trampoline_addr` must hold a copy of `trampoline()` that was cleaned to the point of
+///   coherency
+/// - `phys_parked_flags_addr` must point to `num_cores` words, one per core, the word of the
+///   calling core must be nonzero
+/// - `size` bytes at `phys_image_addr` must be a valid kernel image, with `size` a multiple of 8
+pub unsafe fn boot(phys_trampoline_addr: Address<Physical>, phys_image_addr: Address<Physical>, phys_load_addr: Address<Physical>, size: usize, phys_parked_flags_addr: Address<Physical>, num_cores: usize) -> ! {
+    asm!(
+        "hvc #0",
+        in("x0") phys_trampoline_addr.as_usize() + trampoline_offset(&__chainload_boot),
+        in("x1") phys_image_addr.as_usize(),
+        in("x2") phys_load_addr.as_usize(),
+        in("x3") size,
+        in("x4") phys_parked_flags_addr.as_usize(),
+        in("x5") num_cores,
+        options(noreturn, nostack)
+    )
+}
+
+/// write back this core's caches, set its parked flag and wait in EL2 to be released through the
+/// spin-table again
+///
+/// # safety
+/// - `phys_trampoline_addr` must hold a copy of `trampoline()` that was cleaned to the point of
+///   coherency and stays in place until the core is released
+/// - the spin-table entry at `phys_release_addr` must be zero
+pub unsafe fn park(phys_trampoline_addr: Address<Physical>, phys_release_addr: Address<Physical>, phys_parked_flag_addr: Address<Physical>) -> ! {
+    asm!(
+        "hvc #0",
+        in("x0") phys_trampoline_addr.as_usize() + trampoline_offset(&__chainload_park),
+        in("x1") phys_release_addr.as_usize(),
+        in("x2") phys_parked_flag_addr.as_usize(),
+        options(noreturn, nostack)
+    )
+}
@@ -0,0 +1,174 @@
+// minimal EL2 vector table installed by `_start`, it only exists so that EL1 can get back to EL2
+// to hand the machine over to a chainloaded image
+.section .text
+
+.balign 0x800
+__el2_vectors:
+	// exceptions taken from EL2 itself
+.rept 8
+.balign 0x80
+	b .L_el2_hang
+.endr
+
+// synchronous exception from EL1, i.e. `hvc`: continue at the physical address in x0 with the
+// MMU turned off, x1-x5 are left untouched as arguments
+.balign 0x80
+	br x0
+
+.rept 7
+.balign 0x80
+	b .L_el2_hang
+.endr
+
+.L_el2_hang:
+	wfe
+	b .L_el2_hang
+
+.size __el2_vectors, . - __el2_vectors
+.global __el2_vectors
+
+// everything from here to `__chainload_trampoline_end_exclusive` is copied next to the received
+// image and runs from there in EL2 with the MMU and data cache off, it must be position
+// independent and must not touch the stack
+.balign 8
+__chainload_trampoline_start:
+
+// fn __chainload_boot(self: u64, src: u64, dst: u64, size: u64, parked_flags: u64, num_cores: u64)
+// all addresses physical, `size` is a multiple of 8
+__chainload_boot:
+	mov x12, x1
+	mov x13, x2
+	mov x14, x3
+	mov x15, x4
+	mov x16, x5
+
+	// wait for every core to write back its caches and park
+.L_boot_wait_for_cores:
+	mov x6, xzr
+.L_boot_check_core:
+	ldr x7, [x15, x6, lsl #3]
+	cbz x7, .L_boot_wait_for_cores
+	add x6, x6, #1
+	cmp x6, x16
+	b.lo .L_boot_check_core
+
+	// dirty lines written back after the copy would corrupt the new image
+	bl .L_clean_invalidate_dcache
+
+	add x14, x12, x14
+	mov x6, x13
+.L_boot_copy:
+	cmp x12, x14
+	b.hs .L_boot_copy_done
+	ldr x7, [x12], #8
+	str x7, [x6], #8
+	b .L_boot_copy
+
+.L_boot_copy_done:
+	dsb sy
+	bl .L_reset_el1
+
+	// the firmware would pass the device tree in x0, we don't have one
+	mov x0, xzr
+	mov x1, xzr
+	mov x2, xzr
+	mov x3, xzr
+	br x13
+
+.size __chainload_boot, . - __chainload_boot
+.global __chainload_boot
+
+// fn __chainload_park(self: u64, release_addr: u64, parked_flag: u64)
+// all addresses physical
+__chainload_park:
+	mov x12, x1
+	mov x13, x2
+
+	bl .L_clean_invalidate_dcache
+
+	mov x6, #1
+	str x6, [x13]
+	dsb sy
+
+	// behave like the firmware's spin-table, so the new kernel can release this core as usual
+.L_park_loop:
+	wfe
+	ldr x14, [x12]
+	cbz x14, .L_park_loop
+
+	bl .L_reset_el1
+
+	mov x0, xzr
+	br x14
+
+.size __chainload_park, . - __chainload_park
+.global __chainload_park
+
+// clean and invalidate all data and unified caches by set/way up to the point of coherency
+// clobbers x0-x11
+.L_clean_invalidate_dcache:
+	mrs x0, CLIDR_EL1
+	ubfx x3, x0, #24, #3 // level of coherency
+	lsl x3, x3, #1
+	cbz x3, .L_dcache_done
+	mov x10, xzr // cache level << 1, as expected by CSSELR_EL1 and DC CISW
+
+.L_dcache_level:
+	add x2, x10, x10, lsr #1 // cache level * 3
+	lsr x1, x0, x2
+	and x1, x1, #7 // cache type of this level
+	cmp x1, #2
+	b.lt .L_dcache_next_level // no data cache
+
+	msr CSSELR_EL1, x10
+	isb
+	mrs x1, CCSIDR_EL1
+	and x2, x1, #7
+	add x2, x2, #4 // log2 of the line size
+	ubfx x4, x1, #3, #10 // highest way number
+	clz w5, w4 // position of the way in DC CISW's operand
+	ubfx x7, x1, #13, #15 // highest set number
+
+.L_dcache_set:
+	mov x9, x4
+
+.L_dcache_way:
+	lsl x6, x9, x5
+	orr x11, x10, x6
+	lsl x6, x7, x2
+	orr x11, x11, x6
+	dc cisw, x11
+	subs x9, x9, #1
+	b.ge .L_dcache_way
+	subs x7, x7, #1
+	b.ge .L_dcache_set
+
+.L_dcache_next_level:
+	add x10, x10, #2
+	cmp x3, x10
+	b.gt .L_dcache_level
+
+.L_dcache_done:
+	msr CSSELR_EL1, xzr
+	dsb sy
+	isb
+	ret
+
+// leave EL1 with the MMU and caches off, like the new kernel expects to find it
+.L_reset_el1:
+	mrs x0, SCTLR_EL1
+	bic x0, x0, #(1 << 0) // M
+	bic x0, x0, #(1 << 2) // C
+	bic x0, x0, #(1 << 12) // I
+	msr SCTLR_EL1, x0
+	tlbi alle1
+	ic iallu
+	dsb sy
+	isb
+	ret
+
+.balign 8
+__chainload_trampoline_end_exclusive:
+
+.global __chainload_trampoline_start
+.global __chainload_trampoline_end_exclusive
@@ -1,6 +1,9 @@
 use aarch64_cpu::asm;
+use core::arch::asm;
 
-pub use asm::nop;
+use crate::memory::{Address, Virtual};
+
+pub use asm::{nop, wfi};
 
 #[inline(always)]
 pub fn wait_forever() -> ! {
@@ -8,3 +11,44 @@ pub fn wait_forever() -> ! {
         asm::wfe()
     }
 }
+
+/// make instructions that were written through the data side at `start` visible to instruction
+/// fetches of all cores, no matter through which virtual address they are executed
+pub fn sync_instruction_cache(start: Address<Virtual>, size: usize) {
+    let ctr_el0: u64;
+    unsafe { asm!("mrs {}, CTR_EL0", out(reg) ctr_el0, options(nomem, nostack)) };
+
+    // the line sizes are encoded as log2 of the number of words
+    let dcache_line_size = 4 << ((ctr_el0 >> 16) & 0xf);
+    let icache_line_size = 4 << (ctr_el0 & 0xf);
+
+    let end = start.as_usize() + size;
+
+    for addr in (start.as_usize() & !(dcache_line_size - 1)..end).step_by(dcache_line_size) {
+        unsafe { asm!("dc cvau, {}", in(reg) addr, options(nostack)) };
+    }
+
+    unsafe { asm!("dsb ish", options(nostack)) };
+
+    for addr in (start.as_usize() & !(icache_line_size - 1)..end).step_by(icache_line_size) {
+        unsafe { asm!("ic ivau, {}", in(reg) addr, options(nostack)) };
+    }
+
+    unsafe { asm!("dsb ish", "isb", options(nostack)) };
+}
+
+/// write back and invalidate the data cache lines covering `size` bytes at `start`, so that
+/// observers with caches turned off see the data
+pub fn clean_invalidate_dcache_to_poc(start: Address<Virtual>, size: usize) {
+    let ctr_el0: u64;
+    unsafe { asm!("mrs {}, CTR_EL0", out(reg) ctr_el0, options(nomem, nostack)) };
+
+    let dcache_line_size = 4 << ((ctr_el0 >> 16) & 0xf);
+    let end = start.as_usize() + size;
+
+    for addr in (start.as_usize() & !(dcache_line_size - 1)..end).step_by(dcache_line_size) {
+        unsafe { asm!("dc civac, {}", in(reg) addr, options(nostack)) };
+    }
+
+    unsafe { asm!("dsb sy", options(nostack)) };
+}
@@ -14,7 +14,7 @@ global_asm!(
 /// - the `bss` section is not initialized yet, the code can't use or reference it in any way
 /// - the hw state of EL1 must be prepared in a sound way
 #[inline(always)]
-unsafe fn prepare_el2_to_el1_transition(virt_boot_core_stack_end_exclusive_addr: u64, virt_kernel_init_addr: u64) {
+unsafe fn prepare_el2_to_el1_transition(virt_stack_end_exclusive_addr: u64, virt_kernel_init_addr: u64) {
     // enable timer counter registers for EL1
     CNTHCTL_EL2.write(CNTHCTL_EL2::EL1PCEN::SET + CNTHCTL_EL2::EL1PCTEN::SET);
 
@@ -41,17 +41,19 @@ unsafe fn prepare_el2_to_el1_transition(virt_boot_core_stack_end_exclusive_addr:
 
     // set up SP_EL1 (stack pointer), which will be used by EL1 once we "return" to it. since there
     // are no plans to return to EL2, just re-use the same stack
-    SP_EL1.set(virt_boot_core_stack_end_exclusive_addr);
+    SP_EL1.set(virt_stack_end_exclusive_addr);
 }
 
 /// the rust entry of the `kernel` binary
-/// called from the assembly `_start` function
+/// called from the assembly `_start` function on the boot core and from `_start_secondary` on the
+/// secondary cores
 ///
 /// # safety
-/// - exception return from EL2 must continue execution in EL1 with `kernel_init()`
+/// - exception return from EL2 must continue execution in EL1 with `kernel_init()` or
+///   `kernel_init_secondary()`
 #[no_mangle]
-pub unsafe extern "C" fn _start_rust(phys_kernel_tables_base_addr: u64, virt_boot_core_stack_end_exclusive_addr: u64, virt_kernel_init_addr: u64) -> ! {
-    prepare_el2_to_el1_transition(virt_boot_core_stack_end_exclusive_addr, virt_kernel_init_addr);
+pub unsafe extern "C" fn _start_rust(phys_kernel_tables_base_addr: u64, virt_stack_end_exclusive_addr: u64, virt_kernel_init_addr: u64) -> ! {
+    prepare_el2_to_el1_transition(virt_stack_end_exclusive_addr, virt_kernel_init_addr);
 
     // turn on the MMU for EL1
     let addr = Address::new(phys_kernel_tables_base_addr as usize);
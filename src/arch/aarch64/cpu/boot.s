@@ -59,6 +59,10 @@ _start:
 	b.eq .L_parking_loop
 	str w5, [x4]
 
+	// install the EL2 vectors, used to get back to EL2 for chainloading
+	ADR_REL x6, __el2_vectors // provided by aarch64/chainload.s
+	msr VBAR_EL2, x6
+
 	// jump to rust code, x0, x1 and x2 hold the function arguments provided to _start_rust()
 	b _start_rust
 
@@ -70,3 +74,48 @@ _start:
 .size _start, . - _start
 .type _start, function
 .global _start
+
+// fn _start_secondary()
+// entry point of the secondary cores, released by the boot core through the spin-table
+.section .text._start_secondary
+
+_start_secondary:
+	// only proceed if the core executes in EL2, park otherwise
+	mrs x0, CurrentEL
+	cmp x0, {CONST_CURRENTEL_EL2}
+	b.ne .L_secondary_parking_loop
+
+	// offset of this core's stack end from the start of the secondary core stacks
+	mrs x1, MPIDR_EL1
+	and x1, x1, {CONST_CORE_ID_MASK}
+	ADR_ABS x2, __secondary_core_stack_stride
+	mul x4, x1, x2
+
+	// load the base address of the kernel's translation tables
+	ldr x0, PHYS_KERNEL_TABLES_BASE_ADDR // provided by bsp/*/memory/mmu.rs
+
+	// load the absolute addresses of this core's stack end and the secondary init function
+	ADR_ABS x1, __secondary_core_stacks_start
+	add x1, x1, x4
+	ADR_ABS x2, kernel_init_secondary
+
+	// set the stack pointer ensuring EL2 code can use the stack
+	ADR_REL x3, __secondary_core_stacks_start
+	add x3, x3, x4
+	mov sp, x3
+
+	// install the EL2 vectors, used to get back to EL2 for chainloading
+	ADR_REL x6, __el2_vectors // provided by aarch64/chainload.s
+	msr VBAR_EL2, x6
+
+	// jump to rust code, x0, x1 and x2 hold the function arguments provided to _start_rust()
+	b _start_rust
+
+// wait for events indefinitely
+.L_secondary_parking_loop:
+	wfe
+	b .L_secondary_parking_loop
+
+.size _start_secondary, . - _start_secondary
+.type _start_secondary, function
+.global _start_secondary
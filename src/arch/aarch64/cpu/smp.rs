@@ -1,4 +1,8 @@
-use aarch64_cpu::registers::{MPIDR_EL1, Readable};
+use core::{arch::asm, cell::UnsafeCell};
+
+use aarch64_cpu::{asm::{self as cpu_asm, barrier}, registers::{MPIDR_EL1, Readable}};
+
+use crate::memory::{Address, Virtual};
 
 #[inline(always)]
 #[allow(unused)]
@@ -7,3 +11,32 @@ pub fn core_id<T>() -> T where T: From<u8> {
 
     T::from((MPIDR_EL1.get() & CORE_MASK) as u8)
 }
+
+/// the virtual address of the secondary cores' assembly entry point
+pub fn virt_secondary_core_entry_addr() -> Address<Virtual> {
+    // provided by `boot.s`
+    extern "Rust" {
+        static _start_secondary: UnsafeCell<()>;
+    }
+
+    Address::new(unsafe { _start_secondary.get() as usize })
+}
+
+/// release a core that is parked on a spin-table by writing its entry address into the core's
+/// release address and waking it up
+///
+/// # safety
+/// - `virt_release_addr` must point to the mapped release address of a parked core
+/// - `phys_entry_addr` must be the physical address of a valid entry point for secondary cores
+pub unsafe fn spin_table_release(virt_release_addr: Address<Virtual>, phys_entry_addr: u64) {
+    let release_addr = virt_release_addr.as_usize() as *mut u64;
+
+    core::ptr::write_volatile(release_addr, phys_entry_addr);
+
+    // the parked core polls the release address with its MMU and caches turned off, so the write
+    // must be cleaned to the point of coherency before the core is woken up
+    asm!("dc civac, {addr}", addr = in(reg) release_addr, options(nostack, preserves_flags));
+    barrier::dsb(barrier::SY);
+
+    cpu_asm::sev();
+}
@@ -3,17 +3,37 @@ use core::{arch::global_asm, cell::UnsafeCell, fmt};
 use aarch64_cpu::{asm::barrier, registers::{CurrentEL, Readable, ESR_EL1, FAR_EL1, SPSR_EL1, VBAR_EL1, Writeable}};
 use tock_registers::registers::InMemoryRegister;
 
-use crate::exception;
+use crate::{backtrace::Backtrace, bsp::{self, memory::mmu::KernelGranule}, exception, memory::{Address, Virtual}, sched, syscall, warn};
 use super::PrivilegeLevel;
 
-global_asm!(include_str!("exception.s"));
+global_asm!(
+    include_str!("exception.s"),
+    CONST_PAGE_SIZE = const KernelGranule::SIZE,
+    CONST_CORE_ID_MASK = const 0b11,
+    CONST_EMERGENCY_STACK_SHIFT = const EMERGENCY_STACK_SHIFT,
+    EMERGENCY_STACKS = sym EMERGENCY_STACKS,
+);
+
+/// the stack a core handles an overflow of its kernel stack on is `1 << EMERGENCY_STACK_SHIFT`
+/// bytes in size
+const EMERGENCY_STACK_SHIFT: usize = 14;
+
+#[repr(C, align(16))]
+struct EmergencyStack(UnsafeCell<[u8; 1 << EMERGENCY_STACK_SHIFT]>);
+
+// only ever used as a stack by the core it belongs to, never accessed through the static
+unsafe impl Sync for EmergencyStack {}
+
+static EMERGENCY_STACKS: [EmergencyStack; bsp::cpu::NUM_CORES] = [const { EmergencyStack(UnsafeCell::new([0; 1 << EMERGENCY_STACK_SHIFT])) }; bsp::cpu::NUM_CORES];
 
 #[repr(transparent)]
 struct SpsrEL1(InMemoryRegister<u64, SPSR_EL1::Register>);
 struct EsrEL1(InMemoryRegister<u64, ESR_EL1::Register>);
 
-#[repr(C)]
-struct ExceptionContext {
+/// the register state saved on exception entry, which doubles as the saved context of a thread
+/// that is switched out
+#[repr(C, align(16))]
+pub struct ExceptionContext {
     /// general purpose registers
     gpr: [u64; 30],
 
@@ -28,78 +48,143 @@ struct ExceptionContext {
 
     /// exception syndrome
     esr_el1: EsrEL1,
+
+    /// stack pointer of EL0
+    sp_el0: u64,
 }
 
-fn default_exception_handler(exc: &ExceptionContext) {
-    panic!("CPU exception!\n\n{}", exc);
+fn default_exception_handler(exc: &ExceptionContext) -> ! {
+    panic!("CPU exception!\n\n{}\n\nBacktrace of the interrupted code:\n{}", exc, Backtrace::from_exception_context(exc));
+}
+
+/// the name of the stack whose guard page the data abort described by `exc` hit
+fn overflowed_stack(exc: &ExceptionContext) -> Option<&'static str> {
+    if exc.exception_class() != Some(ESR_EL1::EC::Value::DataAbortCurrentEL) || exc.esr_el1.far_not_valid() {
+        return None;
+    }
+
+    exception::stack_guard::search(Address::new(FAR_EL1.get() as usize))
+}
+
+fn stack_overflow_handler(exc: &ExceptionContext, stack_name: &str) -> ! {
+    panic!(
+        "kernel stack overflow\n\n      Stack: {}\n      PC:    {:#018x}\n      FAR:   {:#018x}\n\nBacktrace of the interrupted code:\n{}",
+        stack_name,
+        exc.program_counter(),
+        FAR_EL1.get(),
+        Backtrace::from_exception_context(exc)
+    );
 }
 
 // EL0
 
 #[no_mangle]
-extern "C" fn current_el0_synchronous(_e: &mut ExceptionContext) {
+extern "C" fn current_el0_synchronous(_e: &mut ExceptionContext) -> *mut ExceptionContext {
     panic!("Should not be here. Use of SP_EL0 in EL1 is not supported.");
 }
 
 #[no_mangle]
-extern "C" fn current_el0_irq(_e: &mut ExceptionContext) {
+extern "C" fn current_el0_irq(_e: &mut ExceptionContext) -> *mut ExceptionContext {
     panic!("Should not be here. Use of SP_EL0 in EL1 is not supported.");
 }
 
 #[no_mangle]
-extern "C" fn current_el0_serror(_e: &mut ExceptionContext) {
+extern "C" fn current_el0_serror(_e: &mut ExceptionContext) -> *mut ExceptionContext {
     panic!("Should not be here. Use of SP_EL0 in EL1 is not supported.");
 }
 
 // ELX
 
 #[no_mangle]
-extern "C" fn current_elx_synchronous(e: &mut ExceptionContext) {
+extern "C" fn current_elx_synchronous(e: &mut ExceptionContext) -> *mut ExceptionContext {
+    if e.exception_class() == Some(ESR_EL1::EC::Value::SVC64) {
+        match e.esr_el1.svc_imm() {
+            sched::SVC_YIELD => return sched::reschedule(e),
+            sched::SVC_PARK => return sched::park_current(e),
+            _ => (),
+        }
+    }
+
+    // an access that is allowed to fault, like a copy from user space, resumes at its fixup
+    if e.exception_class() == Some(ESR_EL1::EC::Value::DataAbortCurrentEL) {
+        if let Some(fixup_pc) = exception::fixup::search(Address::new(e.elr_el1 as usize)) {
+            e.elr_el1 = fixup_pc.as_usize() as u64;
+            return e;
+        }
+    }
+
+    if let Some(stack_name) = overflowed_stack(e) {
+        stack_overflow_handler(e, stack_name);
+    }
+
+    default_exception_handler(e);
+}
+
+/// entered on the emergency stack of the core, for an abort that the interrupted stack might not
+/// have had room for
+#[no_mangle]
+extern "C" fn current_elx_stack_overflow(e: &mut ExceptionContext) -> *mut ExceptionContext {
+    if let Some(stack_name) = overflowed_stack(e) {
+        stack_overflow_handler(e, stack_name);
+    }
+
     default_exception_handler(e);
 }
 
 #[no_mangle]
-extern "C" fn current_elx_irq(_e: &mut ExceptionContext) {
+extern "C" fn current_elx_irq(e: &mut ExceptionContext) -> *mut ExceptionContext {
     let token = unsafe { &exception::asynchronous::IRQContext::new() };
     exception::asynchronous::irq_manager().handle_pending_irqs(token);
+
+    sched::preempt(e)
 }
 
 #[no_mangle]
-extern "C" fn current_elx_serror(e: &mut ExceptionContext) {
+extern "C" fn current_elx_serror(e: &mut ExceptionContext) -> *mut ExceptionContext {
     default_exception_handler(e);
 }
 
 // lower, aarch64
 
 #[no_mangle]
-extern "C" fn lower_aarch64_synchronous(e: &mut ExceptionContext) {
-    default_exception_handler(e);
+extern "C" fn lower_aarch64_synchronous(e: &mut ExceptionContext) -> *mut ExceptionContext {
+    if e.exception_class() == Some(ESR_EL1::EC::Value::SVC64) {
+        return syscall::dispatch(e);
+    }
+
+    // a fault in user space only takes down the offending thread
+    warn!("user thread {} terminated by exception:\n\n{}", sched::current_thread_id().unwrap_or_default(), e);
+
+    sched::kill_current(e)
 }
 
 #[no_mangle]
-extern "C" fn lower_aarch64_irq(e: &mut ExceptionContext) {
-    default_exception_handler(e);
+extern "C" fn lower_aarch64_irq(e: &mut ExceptionContext) -> *mut ExceptionContext {
+    let token = unsafe { &exception::asynchronous::IRQContext::new() };
+    exception::asynchronous::irq_manager().handle_pending_irqs(token);
+
+    sched::preempt(e)
 }
 
 #[no_mangle]
-extern "C" fn lower_aarch64_serror(e: &mut ExceptionContext) {
+extern "C" fn lower_aarch64_serror(e: &mut ExceptionContext) -> *mut ExceptionContext {
     default_exception_handler(e);
 }
 
 // lower, aarch32
 
 #[no_mangle]
-extern "C" fn lower_aarch32_synchronous(e: &mut ExceptionContext) {
+extern "C" fn lower_aarch32_synchronous(e: &mut ExceptionContext) -> *mut ExceptionContext {
     default_exception_handler(e);
 }
 
 #[no_mangle]
-extern "C" fn lower_aarch32_irq(e: &mut ExceptionContext) {
+extern "C" fn lower_aarch32_irq(e: &mut ExceptionContext) -> *mut ExceptionContext {
     default_exception_handler(e);
 }
 
 #[no_mangle]
-extern "C" fn lower_aarch32_serror(e: &mut ExceptionContext) {
+extern "C" fn lower_aarch32_serror(e: &mut ExceptionContext) -> *mut ExceptionContext {
     default_exception_handler(e);
 }
 
@@ -138,25 +223,316 @@ impl EsrEL1 {
     fn exception_class(&self) -> Option<ESR_EL1::EC::Value> {
         self.0.read_as_enum(ESR_EL1::EC)
     }
+
+    /// the immediate of an `svc` instruction, only meaningful for `SVC64`
+    #[inline(always)]
+    fn svc_imm(&self) -> u16 {
+        self.0.read(ESR_EL1::ISS) as u16
+    }
+}
+
+impl EsrEL1 {
+    #[inline(always)]
+    fn iss_bits(&self, offset: usize, num_bits: usize) -> u64 {
+        (self.0.read(ESR_EL1::ISS) >> offset) & ((1 << num_bits) - 1)
+    }
+
+    #[inline(always)]
+    fn iss_bit(&self, offset: usize) -> bool {
+        self.iss_bits(offset, 1) != 0
+    }
+
+    /// whether the fault address register holds an address that is not valid for this abort (FnV)
+    #[inline(always)]
+    fn far_not_valid(&self) -> bool {
+        self.iss_bit(10)
+    }
+
+    fn fmt_data_abort(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "        Fault Status       (DFSC): {:#04x} - {}", self.iss_bits(0, 6), FaultStatus(self.iss_bits(0, 6)))?;
+
+        // cache maintenance instructions always report a write
+        let access = match (self.iss_bit(8), self.iss_bit(6)) {
+            (true, _) => "cache maintenance",
+            (false, true) => "write",
+            (false, false) => "read",
+        };
+        writeln!(f, "        Access       (WnR, CM): {}", access)?;
+
+        if self.iss_bit(24) {
+            let size = match self.iss_bits(22, 2) {
+                0b00 => "byte",
+                0b01 => "halfword",
+                0b10 => "word",
+                _ => "doubleword",
+            };
+            let sign_extended = if self.iss_bit(21) { ", sign extended" } else { "" };
+            let register_width = if self.iss_bit(15) { 'x' } else { 'w' };
+
+            writeln!(f, "        Instruction syndrome:   {} into {}{}{}", size, register_width, self.iss_bits(16, 5), sign_extended)?;
+        }
+
+        self.fmt_abort_common(f)
+    }
+
+    fn fmt_instr_abort(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "        Fault Status       (IFSC): {:#04x} - {}", self.iss_bits(0, 6), FaultStatus(self.iss_bits(0, 6)))?;
+
+        self.fmt_abort_common(f)
+    }
+
+    fn fmt_abort_common(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yes_no = |x| -> _ {
+            if x { "Yes" } else { "No" }
+        };
+
+        writeln!(f, "        Stage 1 table walk (S1PTW): {}", yes_no(self.iss_bit(7)))?;
+        writeln!(f, "        External abort        (EA): {}", yes_no(self.iss_bit(9)))?;
+        writeln!(f, "        FAR_EL1 valid        (FnV): {}", yes_no(!self.far_not_valid()))
+    }
+
+    fn fmt_msr_mrs(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op0 = self.iss_bits(20, 2);
+        let op2 = self.iss_bits(17, 3);
+        let op1 = self.iss_bits(14, 3);
+        let crn = self.iss_bits(10, 4);
+        let rt = self.iss_bits(5, 5);
+        let crm = self.iss_bits(1, 4);
+
+        if self.iss_bit(0) {
+            writeln!(f, "        Instruction: mrs x{}, S{}_{}_C{}_C{}_{}", rt, op0, op1, crn, crm, op2)
+        } else {
+            writeln!(f, "        Instruction: msr S{}_{}_C{}_C{}_{}, x{}", op0, op1, crn, crm, op2, rt)
+        }
+    }
+
+    fn fmt_serror(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the syndrome is implementation defined
+        if self.iss_bit(24) {
+            return writeln!(f, "        Implementation defined syndrome: {:#x}", self.iss_bits(0, 24));
+        }
+
+        let dfsc = match self.iss_bits(0, 6) {
+            0b00_0000 => "uncategorized",
+            0b01_0001 => "asynchronous SError interrupt",
+            _ => "reserved",
+        };
+        writeln!(f, "        Fault Status (DFSC): {}", dfsc)?;
+
+        let aet = match self.iss_bits(10, 3) {
+            0b000 => "uncontainable",
+            0b001 => "unrecoverable state",
+            0b010 => "restartable state",
+            0b011 => "recoverable state",
+            0b110 => "corrected",
+            _ => "reserved",
+        };
+        writeln!(f, "        Error Type    (AET): {}", aet)
+    }
+
+    fn fmt_iss(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ESR_EL1::EC::Value::*;
+
+        let ec = match self.exception_class() {
+            None => return Ok(()),
+            Some(x) => x,
+        };
+
+        match ec {
+            DataAbortLowerEL | DataAbortCurrentEL => self.fmt_data_abort(f),
+            InstrAbortLowerEL | InstrAbortCurrentEL => self.fmt_instr_abort(f),
+            SVC64 | HVC64 | SMC64 => writeln!(f, "        Immediate: #{:#x}", self.iss_bits(0, 16)),
+            Brk64 => writeln!(f, "        Comment: #{:#x}", self.iss_bits(0, 16)),
+            TrappedMsrMrs => self.fmt_msr_mrs(f),
+            SError => self.fmt_serror(f),
+            TrappedWFIorWFE => {
+                let instruction = if self.iss_bits(0, 2) == 0b00 { "wfi" } else { "wfe" };
+                writeln!(f, "        Instruction: {}", instruction)
+            }
+            WatchpointLowerEL | WatchpointCurrentEL => {
+                let access = if self.iss_bit(6) { "write" } else { "read" };
+                writeln!(f, "        Access (WnR): {}", access)
+            }
+            SoftwareStepLowerEL | SoftwareStepCurrentEL if self.iss_bit(24) => {
+                let exclusive = if self.iss_bit(6) { "Yes" } else { "No" };
+                writeln!(f, "        Stepped a load exclusive (EX): {}", exclusive)
+            }
+            BranchTarget => writeln!(f, "        Branch type (BTYPE): {:#b}", self.iss_bits(0, 2)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// a data or instruction fault status code, DFSC or IFSC
+struct FaultStatus(u64);
+
+impl fmt::Display for FaultStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = self.0 & 0b11;
+
+        let (description, has_level) = match self.0 {
+            0b00_0000..=0b00_0011 => ("Address size fault", true),
+            0b00_0100..=0b00_0111 => ("Translation fault", true),
+            0b00_1000..=0b00_1011 => ("Access flag fault", true),
+            0b00_1100..=0b00_1111 => ("Permission fault", true),
+            0b01_0000 => ("Synchronous external abort", false),
+            0b01_0001 => ("Synchronous tag check fault", false),
+            0b01_0100..=0b01_0111 => ("Synchronous external abort on translation table walk", true),
+            0b01_1000 => ("Synchronous parity or ECC error", false),
+            0b01_1100..=0b01_1111 => ("Synchronous parity or ECC error on translation table walk", true),
+            0b10_0001 => ("Alignment fault", false),
+            0b10_0010 => ("Debug exception", false),
+            0b11_0000 => ("TLB conflict abort", false),
+            0b11_0001 => ("Unsupported atomic hardware update fault", false),
+            0b11_0100 => ("Implementation defined fault (lockdown)", false),
+            0b11_0101 => ("Implementation defined fault (unsupported exclusive or atomic access)", false),
+            _ => ("Reserved", false),
+        };
+
+        if has_level {
+            write!(f, "{}, level {}", description, level)
+        } else {
+            write!(f, "{}", description)
+        }
+    }
 }
 
 impl fmt::Display for EsrEL1 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ESR_EL1::EC::Value::*;
+
         writeln!(f, "ESR_EL1: {:#010x}", self.0.get())?;
         write!(f, "    Exception Class         (EC): {:#x}", self.0.read(ESR_EL1::EC))?;
-        
+
         let ec_translation = match self.exception_class() {
-            Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => "Data Abort, current EL",
-            _ => "N/A",
+            Some(Unknown) => "Unknown reason, e.g. an undefined instruction",
+            Some(TrappedWFIorWFE) => "Trapped WFI or WFE",
+            Some(TrappedMCRorMRC) | Some(TrappedMCRorMRC2) | Some(TrappedMCRRorMRRC) | Some(TrappedMRRC) | Some(TrappedLDCorSTC) => "Trapped AArch32 coprocessor access",
+            Some(TrappedFP) => "Trapped SIMD or floating-point access",
+            Some(BranchTarget) => "Branch target exception",
+            Some(IllegalExecutionState) => "Illegal execution state",
+            Some(SVC32) => "SVC, AArch32",
+            Some(SVC64) => "SVC",
+            Some(HVC64) => "HVC",
+            Some(SMC64) => "SMC",
+            Some(TrappedMsrMrs) => "Trapped MSR, MRS or system instruction",
+            Some(TrappedSve) => "Trapped SVE access",
+            Some(PointerAuth) => "Pointer authentication failure",
+            Some(InstrAbortLowerEL) => "Instruction Abort, lower EL",
+            Some(InstrAbortCurrentEL) => "Instruction Abort, current EL",
+            Some(PCAlignmentFault) => "PC alignment fault",
+            Some(DataAbortLowerEL) => "Data Abort, lower EL",
+            Some(DataAbortCurrentEL) => "Data Abort, current EL",
+            Some(SPAlignmentFault) => "SP alignment fault",
+            Some(TrappedFP32) | Some(TrappedFP64) => "Trapped floating-point exception",
+            Some(SError) => "SError interrupt",
+            Some(BreakpointLowerEL) => "Breakpoint, lower EL",
+            Some(BreakpointCurrentEL) => "Breakpoint, current EL",
+            Some(SoftwareStepLowerEL) => "Software step, lower EL",
+            Some(SoftwareStepCurrentEL) => "Software step, current EL",
+            Some(WatchpointLowerEL) => "Watchpoint, lower EL",
+            Some(WatchpointCurrentEL) => "Watchpoint, current EL",
+            Some(Bkpt32) => "BKPT, AArch32",
+            Some(Brk64) => "BRK instruction",
+            None => "N/A",
         };
 
         writeln!(f, " - {}", ec_translation)?;
 
-        writeln!(f, "    Instr Specific Syndrome (ISS): {:#x}", self.0.read(ESR_EL1::ISS))
+        let instruction_length = if self.0.is_set(ESR_EL1::IL) { "32 bit" } else { "16 bit" };
+        writeln!(f, "    Instruction Length      (IL): {}", instruction_length)?;
+
+        writeln!(f, "    Instr Specific Syndrome (ISS): {:#x}", self.0.read(ESR_EL1::ISS))?;
+
+        self.fmt_iss(f)
     }
 }
 
 impl ExceptionContext {
+    /// a context that starts executing `entry` at EL1 with IRQs unmasked once it is restored
+    pub fn new_kernel_thread(entry: extern "C" fn() -> !) -> Self {
+        let spsr_el1 = InMemoryRegister::new(0);
+        spsr_el1.write(
+            SPSR_EL1::D::Masked
+                + SPSR_EL1::A::Masked
+                + SPSR_EL1::I::Unmasked
+                + SPSR_EL1::F::Masked
+                + SPSR_EL1::M::EL1h
+        );
+
+        Self {
+            gpr: [0; 30],
+            lr: 0,
+            elr_el1: entry as usize as u64,
+            spsr_el1: SpsrEL1(spsr_el1),
+            esr_el1: EsrEL1(InMemoryRegister::new(0)),
+            sp_el0: 0,
+        }
+    }
+
+    /// a context that starts executing `entry` at EL0 on the stack ending at `stack_end_exclusive`
+    /// once it is restored
+    pub fn new_user_thread(entry: Address<Virtual>, stack_end_exclusive: Address<Virtual>) -> Self {
+        let spsr_el1 = InMemoryRegister::new(0);
+        spsr_el1.write(
+            SPSR_EL1::D::Masked
+                + SPSR_EL1::A::Masked
+                + SPSR_EL1::I::Unmasked
+                + SPSR_EL1::F::Masked
+                + SPSR_EL1::M::EL0t
+        );
+
+        Self {
+            gpr: [0; 30],
+            lr: 0,
+            elr_el1: entry.as_usize() as u64,
+            spsr_el1: SpsrEL1(spsr_el1),
+            esr_el1: EsrEL1(InMemoryRegister::new(0)),
+            sp_el0: stack_end_exclusive.as_usize() as u64,
+        }
+    }
+
+    /// the ABI version a user thread asked for, passed as the `svc` immediate
+    #[inline(always)]
+    pub fn syscall_abi_version(&self) -> u16 {
+        self.esr_el1.svc_imm()
+    }
+
+    /// the syscall number, passed in x8
+    #[inline(always)]
+    pub fn syscall_number(&self) -> u64 {
+        self.gpr[8]
+    }
+
+    /// the syscall arguments, passed in x0 to x5
+    #[inline(always)]
+    pub fn syscall_args(&self) -> [u64; 6] {
+        [self.gpr[0], self.gpr[1], self.gpr[2], self.gpr[3], self.gpr[4], self.gpr[5]]
+    }
+
+    /// the syscall result, returned in x0
+    #[inline(always)]
+    pub fn set_syscall_result(&mut self, value: u64) {
+        self.gpr[0] = value;
+    }
+
+    /// the instruction that was interrupted or caused the exception
+    #[inline(always)]
+    pub fn program_counter(&self) -> usize {
+        self.elr_el1 as usize
+    }
+
+    #[inline(always)]
+    pub fn link_register(&self) -> usize {
+        self.lr as usize
+    }
+
+    /// the frame pointer, x29
+    #[inline(always)]
+    pub fn frame_pointer(&self) -> usize {
+        self.gpr[29] as usize
+    }
+
     #[inline(always)]
     fn exception_class(&self) -> Option<ESR_EL1::EC::Value> {
         self.esr_el1.exception_class()
@@ -168,13 +544,10 @@ impl ExceptionContext {
 
         match self.exception_class() {
             None => false,
+            Some(InstrAbortLowerEL | InstrAbortCurrentEL | DataAbortLowerEL | DataAbortCurrentEL) => !self.esr_el1.far_not_valid(),
             Some(ec) => matches!(
                 ec,
-                InstrAbortLowerEL
-                    | InstrAbortCurrentEL
-                    | PCAlignmentFault
-                    | DataAbortLowerEL
-                    | DataAbortCurrentEL
+                PCAlignmentFault
                     | WatchpointLowerEL
                     | WatchpointCurrentEL
             ),
@@ -192,6 +565,7 @@ impl fmt::Display for ExceptionContext {
 
         writeln!(f, "{}", self.spsr_el1)?;
         writeln!(f, "ELR_EL1: {:#018x}", self.elr_el1)?;
+        writeln!(f, "SP_EL0:  {:#018x}", self.sp_el0)?;
         writeln!(f)?;
         writeln!(f, "General Purpose Registers:")?;
 
@@ -1,9 +1,10 @@
 /// call the function provided by paramter `\handler` after saving the exeption context
 /// provide the context as the first parameter to `\handler`
+/// `\handler` returns the context to restore, which differs from the saved one on a context switch
 .macro CALL_WITH_CONTEXT handler
 __vector_\handler:
 	// make room on the stack for the exception context
-	sub sp, sp, #16 * 17
+	sub sp, sp, #16 * 18
 
 	// store all general purpose registers on the stack
 	stp x0, x1, [sp, #16 * 0]
@@ -27,8 +28,12 @@ __vector_\handler:
 	mrs x2, SPSR_EL1
 	mrs x3, ESR_EL1
 
+	// add the user stack pointer (SP_EL0), which belongs to the interrupted thread as well
+	mrs x4, SP_EL0
+
 	stp lr, x1, [sp, #16 * 15]
 	stp x2, x3, [sp, #16 * 16]
+	str x4, [sp, #16 * 17]
 
 	// x0 is the first argument for the function called through `\handler`
 	mov x0, sp
@@ -36,6 +41,9 @@ __vector_\handler:
 	// call `\handler`
 	bl \handler
 
+	// continue on the stack holding the context returned by `\handler`
+	mov sp, x0
+
 	// after returning from exception handling code, replay the saved context and return via `eret`
 	b __exception_restore_context
 
@@ -70,7 +78,7 @@ __exception_vector_table_start:
 
 // SP_ELx, x > 0
 .org 0x200
-	CALL_WITH_CONTEXT current_elx_synchronous
+	b __current_elx_synchronous_check_stack
 .org 0x280
 	CALL_WITH_CONTEXT current_elx_irq
 .org 0x300
@@ -101,9 +109,11 @@ __exception_vector_table_start:
 __exception_restore_context:
 	ldr w19, [sp, #16 * 16]
 	ldp lr, x20, [sp, #16 * 15]
+	ldr x21, [sp, #16 * 17]
 	
 	msr SPSR_EL1, x19
 	msr ELR_EL1, x20
+	msr SP_EL0, x21
 
 	ldp x0, x1, [sp, #16 * 0]
 	ldp x2, x3, [sp, #16 * 1]
@@ -121,9 +131,51 @@ __exception_restore_context:
 	ldp x26, x27, [sp, #16 * 13]
 	ldp x28, x29, [sp, #16 * 14]
 
-	add sp, sp, #16 * 17
+	add sp, sp, #16 * 18
 
 	eret
 
 .size __exception_restore_context, . - __exception_restore_context
 .type __exception_restore_context, function
+
+// a kernel stack overflow faults on the guard page below the stack, right where the exception
+// context would be saved. so before saving anything, an abort close to the stack pointer switches
+// to the emergency stack of the core, which is never switched back from
+__current_elx_synchronous_check_stack:
+	// x0 is needed as scratch before it can be saved
+	msr TPIDR_EL1, x0
+
+	// only data aborts taken without a change in exception level
+	mrs x0, ESR_EL1
+	lsr x0, x0, #26
+	cmp x0, #0x25
+	b.ne 1f
+
+	// continue normally unless the fault address is within a page of the stack pointer
+	mrs x0, FAR_EL1
+	sub x0, sp, x0
+	add x0, x0, #{CONST_PAGE_SIZE}
+	cmp x0, #({CONST_PAGE_SIZE} * 2)
+	b.hs 1f
+
+	// the emergency stack of core n ends at EMERGENCY_STACKS + (n + 1) * stack size
+	mrs x0, MPIDR_EL1
+	and x0, x0, {CONST_CORE_ID_MASK}
+	add x0, x0, #1
+	lsl x0, x0, #{CONST_EMERGENCY_STACK_SHIFT}
+	mov sp, x0
+	adrp x0, {EMERGENCY_STACKS}
+	add x0, x0, #:lo12:{EMERGENCY_STACKS}
+	add sp, sp, x0
+
+	mrs x0, TPIDR_EL1
+	b __vector_current_elx_stack_overflow
+
+1:	mrs x0, TPIDR_EL1
+	b __vector_current_elx_synchronous
+
+.size __current_elx_synchronous_check_stack, . - __current_elx_synchronous_check_stack
+.type __current_elx_synchronous_check_stack, function
+
+	CALL_WITH_CONTEXT current_elx_synchronous
+	CALL_WITH_CONTEXT current_elx_stack_overflow
@@ -1,12 +1,12 @@
-use aarch64_cpu::registers::{ID_AA64MMFR0_EL1, MAIR_EL1, SCTLR_EL1, TCR_EL1, TTBR1_EL1};
+use aarch64_cpu::registers::{ID_AA64MMFR0_EL1, MAIR_EL1, SCTLR_EL1, TCR_EL1, TTBR0_EL1, TTBR1_EL1};
 use tock_registers::interfaces::*;
 use crate::memory::mmu::MMUEnableError;
 
-use crate::memory::{Address, Physical};
+use crate::memory::{mmu::PageAddress, Address, Physical, Virtual};
 use crate::{bsp, memory};
 
 use super::TranslationGranule;
-use core::intrinsics::unlikely;
+use core::{arch::asm, intrinsics::unlikely};
 
 use aarch64_cpu::asm::barrier;
 use tock_registers::interfaces::Writeable;
@@ -19,6 +19,7 @@ pub type Granule64KiB = TranslationGranule<{ 64 * 1024 }>;
 pub mod mair {
     pub const DEVICE: u64 = 0;
     pub const NORMAL: u64 = 1;
+    pub const NORMAL_NON_CACHEABLE: u64 = 2;
 }
 
 static MMU: MemoryManagementUnit = MemoryManagementUnit;
@@ -39,6 +40,10 @@ impl MemoryManagementUnit {
     fn set_up_mair(&self) {
         // define the memory types being mapped
         MAIR_EL1.write(
+            // attribute 2 - non-cacheable normal DRAM, for memory shared with other bus masters
+            MAIR_EL1::Attr2_Normal_Outer::NonCacheable +
+            MAIR_EL1::Attr2_Normal_Inner::NonCacheable +
+
             // attribute 1 - cacheable normal DRAM
             MAIR_EL1::Attr1_Normal_Outer::WriteBack_NonTransient_ReadWriteAlloc +
             MAIR_EL1::Attr1_Normal_Inner::WriteBack_NonTransient_ReadWriteAlloc +
@@ -51,7 +56,9 @@ impl MemoryManagementUnit {
     #[inline(always)]
     fn configure_translation_control(&self) {
         let t1sz = (64 - bsp::memory::mmu::KernelVirtAddrSpace::SIZE_SHIFT) as u64;
+        let t0sz = (64 - bsp::memory::mmu::UserVirtAddrSpace::SIZE_SHIFT) as u64;
 
+        // TTBR0 walks stay disabled until the first user address space is activated
         TCR_EL1.write(
             TCR_EL1::TBI1::Used
                 + TCR_EL1::IPS::Bits_40
@@ -62,6 +69,11 @@ impl MemoryManagementUnit {
                 + TCR_EL1::EPD1::EnableTTBR1Walks
                 + TCR_EL1::A1::TTBR1
                 + TCR_EL1::T1SZ.val(t1sz)
+                + TCR_EL1::TG0::KiB_64
+                + TCR_EL1::SH0::Inner
+                + TCR_EL1::ORGN0::WriteBack_ReadAlloc_WriteAlloc_Cacheable
+                + TCR_EL1::IRGN0::WriteBack_ReadAlloc_WriteAlloc_Cacheable
+                + TCR_EL1::T0SZ.val(t0sz)
                 + TCR_EL1::EPD0::DisableTTBR0Walks
         );
     }
@@ -71,6 +83,26 @@ pub fn mmu() -> &'static impl crate::memory::mmu::interface::MMU {
     &MMU
 }
 
+/// make descriptor writes visible to the table walkers of all cores, after pages were mapped that
+/// were invalid before and thus cannot be cached in any TLB
+#[inline(always)]
+pub fn publish_descriptor_writes() {
+    barrier::dsb(barrier::ISHST);
+    barrier::isb(barrier::SY);
+}
+
+/// drop the cached translation of `virt_page_addr` from the TLBs of all cores, after its
+/// descriptor was changed or invalidated
+pub fn invalidate_tlb_page(virt_page_addr: PageAddress<Virtual>) {
+    // the operand holds VA[55:12], independent of the granule
+    let operand = ((virt_page_addr.into_inner().as_usize() >> 12) & ((1 << 44) - 1)) as u64;
+
+    barrier::dsb(barrier::ISHST);
+    unsafe { asm!("tlbi vaae1is, {}", in(reg) operand, options(nostack)) };
+    barrier::dsb(barrier::ISH);
+    barrier::isb(barrier::SY);
+}
+
 
 impl memory::mmu::interface::MMU for MemoryManagementUnit {
     unsafe fn enable_mmu_and_caching(&self, phys_tables_base_addr: Address<Physical>) -> Result<(), MMUEnableError> {
@@ -102,6 +134,25 @@ impl memory::mmu::interface::MMU for MemoryManagementUnit {
         Ok(())
     }
 
+    unsafe fn set_user_translation_tables(&self, phys_tables_base_addr: Option<Address<Physical>>) {
+        match phys_tables_base_addr {
+            None => TCR_EL1.modify(TCR_EL1::EPD0::DisableTTBR0Walks),
+            Some(addr) => {
+                TTBR0_EL1.set_baddr(addr.as_usize() as u64);
+                TCR_EL1.modify(TCR_EL1::EPD0::EnableTTBR0Walks);
+            }
+        }
+
+        // no ASIDs are in use, so drop every cached translation of the previous address space
+        asm!(
+            "isb",
+            "tlbi vmalle1",
+            "dsb nsh",
+            "isb",
+            options(nostack)
+        );
+    }
+
     #[inline(always)]
     fn is_enabled(&self) -> bool {
         SCTLR_EL1.matches_all(SCTLR_EL1::M::Enable)
@@ -2,7 +2,7 @@ use core::convert;
 
 use tock_registers::{register_bitfields, registers::InMemoryRegister, interfaces::{Readable, Writeable}};
 
-use crate::{bsp, memory::{self, mmu::{arch_mmu::{Granule512MiB, Granule64KiB}, AccessPermissions, AttributeFields, MemAttributes, MemoryRegion, PageAddress}, Address, Physical, Virtual}};
+use crate::{bsp, memory::{self, mmu::{arch_mmu::{self, Granule512MiB, Granule64KiB}, AccessPermissions, AttributeFields, MemAttributes, MemoryRegion, PageAddress}, Address, Physical, Virtual}};
 
 register_bitfields! {u64,
     STAGE1_TABLE_DESCRIPTOR [
@@ -133,25 +133,41 @@ impl convert::From<AttributeFields> for tock_registers::fields::FieldValue<u64,
                 STAGE1_PAGE_DESCRIPTOR::SH::InnerShareable
                     + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(crate::memory::mmu::arch_mmu::mair::NORMAL)
             }
+            MemAttributes::NonCacheableDRAM => {
+                STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable
+                    + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(crate::memory::mmu::arch_mmu::mair::NORMAL_NON_CACHEABLE)
+            }
             MemAttributes::Device => {
                 STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable
                     + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(crate::memory::mmu::arch_mmu::mair::DEVICE)
             }
         };
 
-        desc += match attribute_fields.access_permissions {
-            AccessPermissions::ReadOnly => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1,
-            AccessPermissions::ReadWrite => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1
+        desc += match (attribute_fields.access_permissions, attribute_fields.user_accessible) {
+            (AccessPermissions::ReadOnly, false) => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1,
+            (AccessPermissions::ReadWrite, false) => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1,
+            (AccessPermissions::ReadOnly, true) => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1_RL0,
+            (AccessPermissions::ReadWrite, true) => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1_EL0,
+        };
+
+        // the kernel never executes memory that is accessible by user space
+        let (pxn, uxn) = if attribute_fields.user_accessible {
+            (true, attribute_fields.execute_never)
+        } else {
+            (attribute_fields.execute_never, true)
         };
 
-        desc += if attribute_fields.execute_never {
+        desc += if pxn {
             STAGE1_PAGE_DESCRIPTOR::PXN::True
         } else {
             STAGE1_PAGE_DESCRIPTOR::PXN::False
         };
 
-        // always set unprivileged execute-never as long as userspace is not yet implemented
-        desc += STAGE1_PAGE_DESCRIPTOR::UXN::True;
+        desc += if uxn {
+            STAGE1_PAGE_DESCRIPTOR::UXN::True
+        } else {
+            STAGE1_PAGE_DESCRIPTOR::UXN::False
+        };
 
         desc
     }
@@ -163,22 +179,30 @@ impl convert::TryFrom<InMemoryRegister<u64, STAGE1_PAGE_DESCRIPTOR::Register>> f
     fn try_from(desc: InMemoryRegister<u64, STAGE1_PAGE_DESCRIPTOR::Register>) -> Result<AttributeFields, Self::Error> {
         let mem_attributes = match desc.read(STAGE1_PAGE_DESCRIPTOR::AttrIndx) {
             memory::mmu::arch_mmu::mair::NORMAL => MemAttributes::CacheableDRAM,
+            memory::mmu::arch_mmu::mair::NORMAL_NON_CACHEABLE => MemAttributes::NonCacheableDRAM,
             memory::mmu::arch_mmu::mair::DEVICE => MemAttributes::Device,
             _ => return Err("unexpected memory attribute"),
         };
 
-        let access_permissions = match desc.read_as_enum(STAGE1_PAGE_DESCRIPTOR::AP) {
-            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RO_EL1) => AccessPermissions::ReadOnly,
-            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RW_EL1) => AccessPermissions::ReadWrite,
-            _ => return Err("unexpected access permission"),
+        let (access_permissions, user_accessible) = match desc.read_as_enum(STAGE1_PAGE_DESCRIPTOR::AP) {
+            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RO_EL1) => (AccessPermissions::ReadOnly, false),
+            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RW_EL1) => (AccessPermissions::ReadWrite, false),
+            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RO_EL1_RL0) => (AccessPermissions::ReadOnly, true),
+            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RW_EL1_EL0) => (AccessPermissions::ReadWrite, true),
+            None => return Err("unexpected access permission"),
         };
 
-        let execute_never = desc.read(STAGE1_PAGE_DESCRIPTOR::PXN) > 0;
+        let execute_never = if user_accessible {
+            desc.read(STAGE1_PAGE_DESCRIPTOR::UXN) > 0
+        } else {
+            desc.read(STAGE1_PAGE_DESCRIPTOR::PXN) > 0
+        };
 
         Ok(AttributeFields {
             mem_attributes,
             access_permissions,
             execute_never,
+            user_accessible,
         })
     }
 }
@@ -275,6 +299,28 @@ impl<const NUM_TABLES: usize, const START_FROM_TOP: bool> FixedSizeTranslationTa
         Ok(desc)
     }
 
+    #[inline(always)]
+    fn mapped_page_descriptor_mut_from_page_addr(&mut self, virt_page_addr: PageAddress<Virtual>) -> Result<&mut PageDescriptor, &'static str> {
+        let (lvl2_index, lvl3_index) = self.lvl2_lvl3_index_from_page_addr(virt_page_addr)?;
+        let desc = &mut self.lvl3[lvl2_index][lvl3_index];
+
+        if !desc.is_valid() {
+            return Err("virtual page is not mapped");
+        }
+
+        Ok(desc)
+    }
+
+    fn check_region_mapped(&self, virt_region: &MemoryRegion<Virtual>) -> Result<(), &'static str> {
+        for virt_page_addr in virt_region.into_iter() {
+            if !self.page_descriptor_from_page_addr(virt_page_addr)?.is_valid() {
+                return Err("virtual page is not mapped");
+            }
+        }
+
+        Ok(())
+    }
+
     #[inline(always)]
     fn set_page_descriptor_from_page_addr(&mut self, virt_page_addr: PageAddress<Virtual>, new_desc: &PageDescriptor) -> Result<(), &'static str> {
         let (lvl2_index, lvl3_index) = self.lvl2_lvl3_index_from_page_addr(virt_page_addr)?;
@@ -327,6 +373,23 @@ impl<const NUM_TABLES: usize, const START_FROM_TOP: bool> memory::mmu::translati
             self.set_page_descriptor_from_page_addr(virt_page, &new_desc)?;
         }
 
+        arch_mmu::publish_descriptor_writes();
+
+        Ok(())
+    }
+
+    unsafe fn unmap_at(&mut self, virt_region: &MemoryRegion<Virtual>) -> Result<(), &'static str> {
+        assert!(self.initialized, "translation tables not initialized");
+
+        // nothing is touched unless the whole region is mapped
+        self.check_region_mapped(virt_region)?;
+
+        for virt_page_addr in virt_region.into_iter() {
+            *self.mapped_page_descriptor_mut_from_page_addr(virt_page_addr)? = PageDescriptor::new_zeroed();
+
+            arch_mmu::invalidate_tlb_page(virt_page_addr);
+        }
+
         Ok(())
     }
 
@@ -356,4 +419,8 @@ impl<const NUM_TABLES: usize, const START_FROM_TOP: bool> memory::mmu::translati
 
         Ok(phys_page.into_inner() + virt_addr.offset_into_page())
     }
+
+    fn phys_base_address(&self) -> Result<Address<Physical>, &'static str> {
+        memory::mmu::try_kernel_virt_addr_to_phys_addr(self.lvl2.virt_start_addr())
+    }
 }
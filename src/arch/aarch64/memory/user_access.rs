@@ -0,0 +1,43 @@
+use core::arch::global_asm;
+
+global_asm!(include_str!("user_access.s"));
+
+extern "C" {
+    fn __copy_from_user(dst: *mut u8, src: usize, len: usize) -> usize;
+    fn __copy_to_user(dst: usize, src: *const u8, len: usize) -> usize;
+    fn __probe_read_u32(addr: usize, value: *mut u32) -> usize;
+    fn __probe_write_u32(addr: usize, value: u32) -> usize;
+}
+
+/// returns the number of bytes that were not copied
+///
+/// # safety
+/// - `dst` must be valid for `len` bytes of writes
+#[inline(always)]
+pub unsafe fn copy_from_user(dst: *mut u8, src: usize, len: usize) -> usize {
+    __copy_from_user(dst, src, len)
+}
+
+/// returns the number of bytes that were not copied
+///
+/// # safety
+/// - `src` must be valid for `len` bytes of reads
+#[inline(always)]
+pub unsafe fn copy_to_user(dst: usize, src: *const u8, len: usize) -> usize {
+    __copy_to_user(dst, src, len)
+}
+
+#[inline(always)]
+pub fn probe_read_u32(addr: usize) -> Option<u32> {
+    let mut value = 0;
+
+    match unsafe { __probe_read_u32(addr, &mut value) } {
+        0 => Some(value),
+        _ => None,
+    }
+}
+
+#[inline(always)]
+pub fn probe_write_u32(addr: usize, value: u32) -> bool {
+    unsafe { __probe_write_u32(addr, value) == 0 }
+}
@@ -0,0 +1,80 @@
+/// record that a fault of the instruction at `\fault` resumes at `\fixup` instead of panicking
+.macro EXCEPTION_TABLE_ENTRY fault, fixup
+	.pushsection .extable, "a"
+	.balign 8
+	.quad \fault, \fixup
+	.popsection
+.endm
+
+/// fn __copy_from_user(dst: *mut u8, src: usize, len: usize) -> usize
+///
+/// copies byte by byte with unprivileged loads, so that only memory user space may read is read.
+/// returns the number of bytes that were not copied
+.section .text.__copy_from_user
+.global __copy_from_user
+.type __copy_from_user, function
+__copy_from_user:
+	cbz x2, 2f
+1:	ldtrb w3, [x1]
+	EXCEPTION_TABLE_ENTRY 1b, 3f
+	strb w3, [x0], #1
+	add x1, x1, #1
+	subs x2, x2, #1
+	b.ne 1b
+2:	mov x0, #0
+	ret
+3:	mov x0, x2
+	ret
+.size __copy_from_user, . - __copy_from_user
+
+/// fn __copy_to_user(dst: usize, src: *const u8, len: usize) -> usize
+///
+/// copies byte by byte with unprivileged stores, so that only memory user space may write is
+/// written. returns the number of bytes that were not copied
+.section .text.__copy_to_user
+.global __copy_to_user
+.type __copy_to_user, function
+__copy_to_user:
+	cbz x2, 2f
+1:	ldrb w3, [x1], #1
+4:	sttrb w3, [x0]
+	EXCEPTION_TABLE_ENTRY 4b, 3f
+	add x0, x0, #1
+	subs x2, x2, #1
+	b.ne 1b
+2:	mov x0, #0
+	ret
+3:	mov x0, x2
+	ret
+.size __copy_to_user, . - __copy_to_user
+
+/// fn __probe_read_u32(addr: usize, value: *mut u32) -> usize
+///
+/// returns zero if the load succeeded, one if it faulted
+.section .text.__probe_read_u32
+.global __probe_read_u32
+.type __probe_read_u32, function
+__probe_read_u32:
+1:	ldr w2, [x0]
+	EXCEPTION_TABLE_ENTRY 1b, 2f
+	str w2, [x1]
+	mov x0, #0
+	ret
+2:	mov x0, #1
+	ret
+.size __probe_read_u32, . - __probe_read_u32
+
+/// fn __probe_write_u32(addr: usize, value: u32) -> usize
+///
+/// returns zero if the store succeeded, one if it faulted
+.section .text.__probe_write_u32
+.global __probe_write_u32
+.type __probe_write_u32, function
+__probe_write_u32:
+1:	str w1, [x0]
+	EXCEPTION_TABLE_ENTRY 1b, 2f
+	mov x0, #0
+	ret
+2:	mov x0, #1
+	ret
+.size __probe_write_u32, . - __probe_write_u32
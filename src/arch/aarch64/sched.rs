@@ -0,0 +1,32 @@
+use core::arch::asm;
+
+/// `svc` immediate used by kernel threads to give up the rest of their time slice
+pub const SVC_YIELD: u16 = 0;
+
+/// `svc` immediate used by kernel threads to block until they are woken up
+pub const SVC_PARK: u16 = 1;
+
+/// enter the scheduler through a synchronous exception, the current context is saved and resumed
+/// once the thread gets scheduled again
+#[inline(always)]
+pub fn yield_now() {
+    unsafe {
+        asm!(
+            "svc {imm}",
+            imm = const SVC_YIELD,
+            options(nomem, nostack)
+        );
+    }
+}
+
+/// enter the scheduler like `yield_now()`, but leave the current thread blocked until it is woken
+#[inline(always)]
+pub fn park_now() {
+    unsafe {
+        asm!(
+            "svc {imm}",
+            imm = const SVC_PARK,
+            options(nomem, nostack)
+        );
+    }
+}
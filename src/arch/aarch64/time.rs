@@ -110,3 +110,23 @@ pub fn spin_for(duration: Duration) {
 
     while GenericTimerCounterValue(CNTPCT_EL0.get()) < counter_value_target {}
 }
+
+/// arm the EL1 physical timer to raise its IRQ once the uptime reaches `deadline`, a deadline
+/// in the past raises it right away
+pub fn set_timeout_irq_at(deadline: Duration) {
+    let counter_value_target: GenericTimerCounterValue = match deadline.try_into() {
+        Err(msg) => {
+            warn!("set_timeout_irq_at: {}. Skipping!", msg);
+            return;
+        }
+        Ok(val) => val
+    };
+
+    CNTP_CVAL_EL0.set(counter_value_target.0);
+    CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::CLEAR);
+}
+
+/// disarm the EL1 physical timer, which also deasserts its IRQ
+pub fn conclude_timeout_irq() {
+    CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::CLEAR);
+}
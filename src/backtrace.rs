@@ -0,0 +1,135 @@
+#[cfg(target_arch = "aarch64")]
+#[path = "arch/aarch64/backtrace.rs"]
+mod arch_backtrace;
+
+use core::{fmt, mem::size_of};
+
+use arch_backtrace::FrameRecord;
+
+use crate::{bsp, exception::ExceptionContext, memory::{Address, Virtual}, symbols};
+
+/// the most frames printed, in case a corrupted chain keeps looking valid
+const MAX_FRAMES: usize = 32;
+
+#[derive(Copy, Clone)]
+pub enum Frame {
+    /// the instruction that was executing
+    ProgramCounter(Address<Virtual>),
+
+    /// the instruction a call returns to
+    ReturnAddress(Address<Virtual>),
+}
+
+/// a call chain, walked lazily along the frame records on the stack
+#[derive(Copy, Clone)]
+pub struct Backtrace {
+    pc: Option<Address<Virtual>>,
+
+    /// the link register of an interrupted function, which may not have pushed a record yet
+    lr: Option<Address<Virtual>>,
+
+    /// address of the next frame record, zero once the chain ends
+    fp: usize,
+}
+
+impl Backtrace {
+    /// the call chain leading to the caller
+    #[inline(always)]
+    pub fn current() -> Self {
+        Self {
+            pc: Some(Address::new(arch_backtrace::program_counter())),
+            lr: None,
+            fp: arch_backtrace::frame_pointer(),
+        }
+    }
+
+    /// the call chain of the code `context` interrupted, which must have been running in the
+    /// kernel
+    pub fn from_exception_context(context: &ExceptionContext) -> Self {
+        Self {
+            pc: Some(Address::new(context.program_counter())),
+            lr: Some(Address::new(context.link_register())),
+            fp: context.frame_pointer(),
+        }
+    }
+
+    /// a record is only trusted if it lies completely within memory a kernel stack can live in,
+    /// so following a corrupted chain never faults
+    fn frame_record(&self) -> Option<FrameRecord> {
+        if self.fp == 0 || self.fp % arch_backtrace::FRAME_RECORD_ALIGN != 0 {
+            return None;
+        }
+
+        let start = Address::<Virtual>::new(self.fp);
+        let end_inclusive = start + (size_of::<FrameRecord>() - 1);
+
+        let mut regions = bsp::memory::mmu::virt_stack_holding_regions();
+        if !regions.any(|region| region.contains(start) && region.contains(end_inclusive)) {
+            return None;
+        }
+
+        Some(unsafe { core::ptr::read(self.fp as *const FrameRecord) })
+    }
+}
+
+impl Iterator for Backtrace {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(pc) = self.pc.take() {
+            return Some(Frame::ProgramCounter(pc));
+        }
+
+        let record = match self.frame_record() {
+            None => return self.lr.take().map(Frame::ReturnAddress),
+            Some(x) => x,
+        };
+
+        // a link register that the first record repeats is not reported twice
+        if let Some(lr) = self.lr.take() {
+            if lr.as_usize() != record.return_address {
+                return Some(Frame::ReturnAddress(lr));
+            }
+        }
+
+        // stacks grow down, so every caller's record lies above the one of its callee
+        self.fp = if record.previous > self.fp { record.previous } else { 0 };
+
+        if record.return_address == 0 {
+            self.fp = 0;
+            return None;
+        }
+
+        Some(Frame::ReturnAddress(Address::new(record.return_address)))
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // a return address already points behind the call, which may be the last instruction of
+        // a function that never returns
+        let (addr, lookup_addr) = match *self {
+            Self::ProgramCounter(addr) => (addr, addr),
+            Self::ReturnAddress(addr) => (addr, Address::new(addr.as_usize() - arch_backtrace::CALL_INSTRUCTION_SIZE)),
+        };
+
+        match symbols::lookup(lookup_addr) {
+            None => write!(f, "{} | <unknown>", addr),
+            Some((name, offset)) => write!(f, "{} | {}+{:#x}", addr, name, offset + (addr.as_usize() - lookup_addr.as_usize())),
+        }
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, frame) in self.take(MAX_FRAMES).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "      {:>2}: {}", i, frame)?;
+        }
+
+        Ok(())
+    }
+}
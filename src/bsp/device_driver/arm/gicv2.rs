@@ -59,6 +59,14 @@ impl driver::interface::DeviceDriver for GICv2 {
 
         Ok(())
     }
+
+    unsafe fn init_secondary_core(&self) -> Result<(), &'static str> {
+        // the distributor is shared and was set up by the boot core, only the cpu interface is banked
+        self.gicc.priority_accept_all();
+        self.gicc.enable();
+
+        Ok(())
+    }
 }
 
 impl exception::asynchronous::interface::IRQManager for GICv2 {
@@ -104,8 +112,15 @@ impl exception::asynchronous::interface::IRQManager for GICv2 {
     fn print_handler(&self) {
         use crate::info;
 
-        info!("    peripheral handler:");
         self.handler_table.read(|table| {
+            info!("    private handler:");
+            for (i, opt) in table.iter().take(32).enumerate() {
+                if let Some(handler) = opt {
+                    info!("        {: >3}. {}", i, handler.name());
+                }
+            }
+
+            info!("    peripheral handler:");
             for (i, opt) in table.iter().skip(32).enumerate() {
                 if let Some(handler) = opt {
                     info!("        {: >3}. {}", i + 32, handler.name());
@@ -1,4 +1,4 @@
-use crate::{bsp::device_driver::common::MMIODerefWrapper, memory::{Address, Virtual}, state, synchronization::{interface::Mutex, IRQSafeNullLock}};
+use crate::{bsp::device_driver::common::MMIODerefWrapper, memory::{Address, Virtual}, state, synchronization::{interface::Mutex, IRQSafeSpinLock}};
 
 use aarch64_cpu::registers::{Readable, Writeable};
 use tock_registers::{register_bitfields, register_structs, registers::{ReadOnly, ReadWrite}};
@@ -53,7 +53,7 @@ type SharedRegisters = MMIODerefWrapper<SharedRegisterBlock>;
 type BankedRegisters = MMIODerefWrapper<BankedRegisterBlock>;
 
 pub struct GICD {
-    shared_registers: IRQSafeNullLock<SharedRegisters>,
+    shared_registers: IRQSafeSpinLock<SharedRegisters>,
     banked_registers: BankedRegisters,
 }
 
@@ -78,7 +78,7 @@ impl GICD {
     /// - the user must ensure to provide a correct MMIO start address
     pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
         Self {
-            shared_registers: IRQSafeNullLock::new(SharedRegisters::new(mmio_start_addr)),
+            shared_registers: IRQSafeSpinLock::new(SharedRegisters::new(mmio_start_addr)),
             banked_registers: BankedRegisters::new(mmio_start_addr),
         }
     }
@@ -1,11 +1,17 @@
+mod bcm2xxx_framebuffer;
 mod bcm2xxx_gpio;
+mod bcm2xxx_mailbox;
 mod bcm2xxx_pl011_uart;
+mod bcm2xxx_watchdog;
 
 #[cfg(feature = "bsp_rpi3")]
 mod bcm2xxx_interrupt_controller;
 
+pub use bcm2xxx_framebuffer::*;
 pub use bcm2xxx_gpio::*;
+pub use bcm2xxx_mailbox::*;
 pub use bcm2xxx_pl011_uart::*;
+pub use bcm2xxx_watchdog::*;
 
 #[cfg(feature = "bsp_rpi3")]
 pub use bcm2xxx_interrupt_controller::*;
@@ -0,0 +1,188 @@
+use super::{property_tag::{pixel_order, AllocateFramebuffer, Dimensions, GetPitch, Offset, SetDepth, SetPhysicalSize, SetPixelOrder, SetVirtualOffset, SetVirtualSize}, Mailbox, Message};
+use crate::{
+    driver, exception::asynchronous::IRQNumber, framebuffer::{self, Info, Surface}, memory::{self, mmu::{AccessPermissions, AttributeFields, MMIODescriptor, MemAttributes, MemoryRegion}, Address, Virtual}, synchronization::{interface::Mutex, SpinLock}, warn
+};
+
+/// the firmware hands out bus addresses, the ARM sees the same memory without the alias bits
+const BUS_ADDR_MASK: u32 = 0x3FFF_FFFF;
+
+/// one buffer is shown while the other one is drawn
+const NUM_BUFFERS: usize = 2;
+
+const BUFFER_ALIGNMENT: u32 = 4096;
+
+struct VideoCoreFramebufferInner {
+    info: Info,
+    virt_start_addr: Address<Virtual>,
+
+    /// the buffer that isn't shown
+    back: usize,
+}
+
+/// a framebuffer allocated by the VideoCore firmware, stacking the buffers vertically in one
+/// virtual surface and flipping between them by moving the virtual offset
+///
+/// drawing takes a while on uncached memory, so the lock leaves IRQs unmasked and the
+/// framebuffer must not be used from IRQ context
+pub struct VideoCoreFramebuffer {
+    mailbox: &'static Mailbox,
+    width: usize,
+    height: usize,
+    depth: usize,
+    inner: SpinLock<Option<VideoCoreFramebufferInner>>,
+}
+
+impl VideoCoreFramebufferInner {
+    fn buffer_size(&self) -> usize {
+        self.info.height * self.info.pitch
+    }
+
+    fn back_buffer(&mut self) -> &mut [u8] {
+        let start = self.virt_start_addr.as_usize() + self.back * self.buffer_size();
+
+        unsafe { core::slice::from_raw_parts_mut(start as *mut u8, self.buffer_size()) }
+    }
+}
+
+impl VideoCoreFramebuffer {
+    pub const COMPATIBLE: &'static str = "VideoCore Framebuffer";
+
+    /// `depth` is the number of bits per pixel, 16 or 32
+    pub const fn new(mailbox: &'static Mailbox, width: usize, height: usize, depth: usize) -> Self {
+        Self {
+            mailbox,
+            width,
+            height,
+            depth,
+            inner: SpinLock::new(None),
+        }
+    }
+
+    /// whether the firmware gave us a framebuffer during init
+    pub fn is_available(&self) -> bool {
+        self.inner.lock(|inner| inner.is_some())
+    }
+
+    fn allocate(&self) -> Result<VideoCoreFramebufferInner, &'static str> {
+        let mut message = Message::new();
+
+        let physical_size = message.push::<SetPhysicalSize>(Dimensions { width: self.width as u32, height: self.height as u32 })?;
+        let virtual_size = message.push::<SetVirtualSize>(Dimensions { width: self.width as u32, height: (self.height * NUM_BUFFERS) as u32 })?;
+        let depth = message.push::<SetDepth>(self.depth as u32)?;
+        message.push::<SetPixelOrder>(pixel_order::RGB)?;
+        message.push::<SetVirtualOffset>(Offset { x: 0, y: 0 })?;
+        let allocation = message.push::<AllocateFramebuffer>(BUFFER_ALIGNMENT)?;
+        let pitch = message.push::<GetPitch>(())?;
+
+        self.mailbox.call(&mut message)?;
+
+        let physical_size = message.response(&physical_size)?;
+        let virtual_size = message.response(&virtual_size)?;
+        let allocation = message.response(&allocation)?;
+
+        if physical_size.width as usize != self.width || physical_size.height as usize != self.height {
+            return Err("firmware refused the resolution");
+        }
+
+        if (virtual_size.height as usize) < self.height * NUM_BUFFERS {
+            return Err("firmware refused the virtual size needed for double buffering");
+        }
+
+        if message.response(&depth)? as usize != self.depth {
+            return Err("firmware refused the depth");
+        }
+
+        let info = Info {
+            width: self.width,
+            height: self.height,
+            pitch: message.response(&pitch)? as usize,
+            depth: self.depth,
+            num_buffers: NUM_BUFFERS,
+        };
+
+        if allocation.base == 0 || (allocation.size as usize) < info.pitch * info.height * NUM_BUFFERS {
+            return Err("firmware did not allocate the buffers");
+        }
+
+        let phys_start_addr = Address::new((allocation.base & BUS_ADDR_MASK) as usize);
+        let phys_region = MemoryRegion::from(MMIODescriptor::new(phys_start_addr, allocation.size as usize));
+
+        // the VideoCore scans out of memory, so it must not be cached, but it isn't a device either
+        let attr = AttributeFields {
+            mem_attributes: MemAttributes::NonCacheableDRAM,
+            access_permissions: AccessPermissions::ReadWrite,
+            execute_never: true,
+            user_accessible: false,
+        };
+
+        let virt_region = unsafe { memory::mmu::kernel_map(Self::COMPATIBLE, &phys_region, &attr)? };
+
+        Ok(VideoCoreFramebufferInner {
+            info,
+            virt_start_addr: virt_region.start_addr() + phys_start_addr.offset_into_page(),
+            back: 1,
+        })
+    }
+}
+
+impl framebuffer::interface::Framebuffer for VideoCoreFramebuffer {
+    fn info(&self) -> Info {
+        self.inner.lock(|inner| inner.as_ref().map(|inner| inner.info)).unwrap_or(Info {
+            width: 0,
+            height: 0,
+            pitch: 0,
+            depth: self.depth,
+            num_buffers: 0,
+        })
+    }
+
+    fn draw(&self, f: &mut dyn FnMut(&mut Surface)) {
+        self.inner.lock(|inner| {
+            if let Some(inner) = inner {
+                let (info, back) = (inner.info, inner.back);
+
+                f(&mut Surface::new(info, back, inner.back_buffer()));
+            }
+        })
+    }
+
+    fn flip(&self) -> Result<(), &'static str> {
+        let (back, offset) = self.inner.lock(|inner| {
+            let inner = inner.as_ref().ok_or("no framebuffer allocated")?;
+
+            Ok((inner.back, Offset { x: 0, y: (inner.back * inner.info.height) as u32 }))
+        })?;
+
+        // the mailbox round trip waits for the VideoCore, so it is made without holding the lock
+        if self.mailbox.property::<SetVirtualOffset>(offset)? != offset {
+            return Err("firmware refused the virtual offset");
+        }
+
+        self.inner.lock(|inner| {
+            // a concurrent flip may have moved on already
+            if let Some(inner) = inner.as_mut().filter(|inner| inner.back == back) {
+                inner.back = (back + 1) % NUM_BUFFERS;
+            }
+        });
+
+        Ok(())
+    }
+}
+
+impl driver::interface::DeviceDriver for VideoCoreFramebuffer {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    /// boards without a display keep working, they just don't get a framebuffer
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        match self.allocate() {
+            Err(x) => warn!("no framebuffer: {}", x),
+            Ok(allocated) => self.inner.lock(|inner| *inner = Some(allocated)),
+        }
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, driver, exception::asynchronous::IRQNumber, memory::{Address, Virtual}, synchronization::{interface::Mutex, IRQSafeNullLock}
+    bsp::device_driver::common::MMIODerefWrapper, driver, exception::asynchronous::IRQNumber, memory::{Address, Virtual}, synchronization::{interface::Mutex, IRQSafeSpinLock}
 };
 
 use tock_registers::{
@@ -77,7 +77,7 @@ struct GPIOInner {
 }
 
 pub struct GPIO {
-    inner: IRQSafeNullLock<GPIOInner>,
+    inner: IRQSafeSpinLock<GPIOInner>,
 }
 
 impl GPIOInner {
@@ -132,7 +132,7 @@ impl GPIO {
 
     pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
         Self {
-            inner: IRQSafeNullLock::new(GPIOInner::new(mmio_start_addr))
+            inner: IRQSafeSpinLock::new(GPIOInner::new(mmio_start_addr))
         }
     }
 
@@ -2,6 +2,7 @@ use core::fmt;
 
 use crate::{bsp::device_driver::common::BoundedUsize, driver, exception::{self, asynchronous::IRQHandlerDescriptor}, memory::{Address, Virtual}};
 
+mod local_ic;
 mod peripheral_ic;
 
 struct PendingIRQs {
@@ -19,6 +20,7 @@ pub enum IRQNumber {
 }
 
 pub struct InterruptController {
+    local_ic: local_ic::LocalIC,
     peripheral_ic: peripheral_ic::PeripheralIC,
 }
 
@@ -52,18 +54,40 @@ impl fmt::Display for IRQNumber {
 }
 
 impl InterruptController {
-    const MAX_LOCAL_IRQ_NUMBER: usize = 3;
+    const MAX_LOCAL_IRQ_NUMBER: usize = 7;
     const MAX_PERIPHERAL_IRQ_NUMBER: usize = 63;
 
     pub const COMPATIBLE: &'static str = "BCM Interrupt Controller";
 
     /// # safety
     /// - the user must ensure to provide a correct MMIO start address
-    pub const unsafe fn new(peripheral_ic_mmio_start_addr: Address<Virtual>) -> Self {
+    pub const unsafe fn new(local_ic_mmio_start_addr: Address<Virtual>, peripheral_ic_mmio_start_addr: Address<Virtual>) -> Self {
         Self {
+            local_ic: local_ic::LocalIC::new(local_ic_mmio_start_addr),
             peripheral_ic: peripheral_ic::PeripheralIC::new(peripheral_ic_mmio_start_addr),
         }
     }
+
+    /// the local IRQ raised when mailbox `mailbox` of a core receives bits
+    #[allow(unused)]
+    pub const fn mailbox_irq(mailbox: usize) -> LocalIRQ {
+        assert!(mailbox < local_ic::LocalIC::NUM_MAILBOXES);
+
+        LocalIRQ::new(local_ic::LocalIC::FIRST_MAILBOX_IRQ + mailbox)
+    }
+
+    /// signal core `core_id` by setting `bits` in one of its mailboxes
+    #[allow(unused)]
+    pub fn send_mailbox(&self, core_id: usize, mailbox: usize, bits: u32) {
+        self.local_ic.send_mailbox(core_id, mailbox, bits);
+    }
+
+    /// the bits set in mailbox `mailbox` of the current core, meant to be called from the mailbox
+    /// IRQ handler. the bits are cleared once the handler returns
+    #[allow(unused)]
+    pub fn mailbox_pending(&self, mailbox: usize) -> u32 {
+        self.local_ic.mailbox_pending(mailbox)
+    }
 }
 
 impl driver::interface::DeviceDriver for InterruptController {
@@ -74,6 +98,7 @@ impl driver::interface::DeviceDriver for InterruptController {
     }
 
     unsafe fn init(&self) -> Result<(), &'static str> {
+        self.local_ic.init();
         self.peripheral_ic.init();
 
         Ok(())
@@ -85,7 +110,15 @@ impl exception::asynchronous::interface::IRQManager for InterruptController {
 
     fn register_handler(&self, irq_handler_descriptor: exception::asynchronous::IRQHandlerDescriptor<Self::IRQNumberType>) -> Result<(), &'static str> {
         match irq_handler_descriptor.number() {
-            IRQNumber::Local(_) => unimplemented!("local IRQ controller not implemented"),
+            IRQNumber::Local(lirq) => {
+                let local_descriptor = IRQHandlerDescriptor::new(
+                    lirq,
+                    irq_handler_descriptor.name(),
+                    irq_handler_descriptor.handler(),
+                );
+
+                self.local_ic.register_handler(local_descriptor)
+            }
             IRQNumber::Peripheral(pirq) => {
                 let peripheral_descriptor = IRQHandlerDescriptor::new(
                     pirq,
@@ -100,17 +133,23 @@ impl exception::asynchronous::interface::IRQManager for InterruptController {
 
     fn enable(&self, irq: &Self::IRQNumberType) {
         match irq {
-            IRQNumber::Local(_) => unimplemented!("local IRQ controller not implemented"),
+            IRQNumber::Local(lirq) => self.local_ic.enable(lirq),
             IRQNumber::Peripheral(pirq) => self.peripheral_ic.enable(pirq),
         }
     }
 
     fn handle_pending_irqs<'irq_context>(&'irq_context self, ic: &exception::asynchronous::IRQContext<'irq_context>) {
-        // can only be a peripheral IRQ cause enable() doesn't support local IRQs
-        self.peripheral_ic.handle_pending_irqs(ic)
+        // local IRQs first, the timer and mailboxes are the latency critical ones
+        self.local_ic.handle_pending_irqs(ic);
+
+        // peripheral IRQs are only routed to the boot core
+        if self.local_ic.is_peripheral_pending() {
+            self.peripheral_ic.handle_pending_irqs(ic)
+        }
     }
 
     fn print_handler(&self) {
+        self.local_ic.print_handler();
         self.peripheral_ic.print_handler();
     }
 }
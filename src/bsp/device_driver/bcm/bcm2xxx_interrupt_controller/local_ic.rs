@@ -0,0 +1,190 @@
+use super::{LocalIRQ, PendingIRQs};
+use crate::{
+    bsp::{self, device_driver::common::MMIODerefWrapper}, cpu, exception, memory::{Address, Virtual}, synchronization::{interface::ReadWriteEx, InitStateLock}
+};
+use alloc::vec::Vec;
+
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_bitfields,
+    register_structs,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
+};
+
+register_bitfields! {
+    u32,
+
+    /// GPU Interrupts Routing Register
+    GPU_INTERRUPTS_ROUTING [
+        IRQCore OFFSET(0) NUMBITS(2) [],
+        FIQCore OFFSET(2) NUMBITS(2) []
+    ],
+
+    /// Core Interrupt Source Register
+    CORE_IRQ_SOURCE [
+        Timers OFFSET(0) NUMBITS(4) [],
+        Mailboxes OFFSET(4) NUMBITS(4) [],
+        GPU OFFSET(8) NUMBITS(1) []
+    ],
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => _reserved1),
+        (0x0C => GPU_INTERRUPTS_ROUTING: ReadWrite<u32, GPU_INTERRUPTS_ROUTING::Register>),
+        (0x10 => _reserved2),
+        (0x40 => CORE_TIMER_INTERRUPT_CONTROL: [ReadWrite<u32>; 4]),
+        (0x50 => CORE_MAILBOX_INTERRUPT_CONTROL: [ReadWrite<u32>; 4]),
+        (0x60 => CORE_IRQ_SOURCE: [ReadOnly<u32, CORE_IRQ_SOURCE::Register>; 4]),
+        (0x70 => _reserved3),
+        (0x80 => CORE_MAILBOX_SET: [WriteOnly<u32>; 16]),
+        (0xC0 => CORE_MAILBOX_READ_CLEAR: [ReadWrite<u32>; 16]),
+        (0x100 => @END),
+    }
+}
+
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+type HandlerTable = Vec<Option<exception::asynchronous::IRQHandlerDescriptor<LocalIRQ>>>;
+
+/// the BCM2836 ARM-local interrupt controller, which routes the generic timer and mailbox IRQs of
+/// each core as well as the GPU IRQ
+///
+/// every core only touches its own banked control, source and mailbox registers, so no lock is
+/// needed. local IRQ numbers follow the bit positions of the core interrupt source register
+pub struct LocalIC {
+    registers: Registers,
+    handler_table: InitStateLock<HandlerTable>,
+}
+
+impl LocalIC {
+    pub const FIRST_TIMER_IRQ: usize = 0;
+    pub const FIRST_MAILBOX_IRQ: usize = 4;
+    pub const NUM_MAILBOXES: usize = 4;
+
+    /// # safety
+    /// - the user must ensure to provide a correct MMIO start address
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            handler_table: InitStateLock::new(Vec::new()),
+        }
+    }
+
+    pub fn init(&self) {
+        self.handler_table.write(|table| table.resize(LocalIRQ::MAX_INCLUSIVE + 1, None));
+
+        // peripheral IRQs are only ever handled by the boot core
+        self.registers.GPU_INTERRUPTS_ROUTING.write(
+            GPU_INTERRUPTS_ROUTING::IRQCore.val(bsp::cpu::BOOT_CORE_ID as u32)
+                + GPU_INTERRUPTS_ROUTING::FIQCore.val(bsp::cpu::BOOT_CORE_ID as u32)
+        );
+    }
+
+    /// whether a peripheral IRQ is routed to and pending on the current core
+    pub fn is_peripheral_pending(&self) -> bool {
+        let core_id = cpu::smp::core_id::<usize>();
+
+        self.registers.CORE_IRQ_SOURCE[core_id].is_set(CORE_IRQ_SOURCE::GPU)
+    }
+
+    /// set `bits` in mailbox `mailbox` of core `core_id`, raising its mailbox IRQ if enabled
+    pub fn send_mailbox(&self, core_id: usize, mailbox: usize, bits: u32) {
+        assert!(core_id < bsp::cpu::NUM_CORES, "invalid core id");
+        assert!(mailbox < Self::NUM_MAILBOXES, "invalid mailbox");
+
+        self.registers.CORE_MAILBOX_SET[core_id * Self::NUM_MAILBOXES + mailbox].set(bits);
+    }
+
+    /// the bits currently set in mailbox `mailbox` of the current core
+    pub fn mailbox_pending(&self, mailbox: usize) -> u32 {
+        assert!(mailbox < Self::NUM_MAILBOXES, "invalid mailbox");
+
+        let core_id = cpu::smp::core_id::<usize>();
+
+        self.registers.CORE_MAILBOX_READ_CLEAR[core_id * Self::NUM_MAILBOXES + mailbox].get()
+    }
+
+    fn clear_mailbox(&self, mailbox: usize, bits: u32) {
+        let core_id = cpu::smp::core_id::<usize>();
+
+        self.registers.CORE_MAILBOX_READ_CLEAR[core_id * Self::NUM_MAILBOXES + mailbox].set(bits);
+    }
+
+    fn pending_irqs(&self) -> PendingIRQs {
+        let core_id = cpu::smp::core_id::<usize>();
+        let source = &self.registers.CORE_IRQ_SOURCE[core_id];
+
+        let pending_mask = (source.read(CORE_IRQ_SOURCE::Timers) << Self::FIRST_TIMER_IRQ)
+            | (source.read(CORE_IRQ_SOURCE::Mailboxes) << Self::FIRST_MAILBOX_IRQ);
+
+        PendingIRQs::new(u64::from(pending_mask))
+    }
+}
+
+impl exception::asynchronous::interface::IRQManager for LocalIC {
+    type IRQNumberType = LocalIRQ;
+
+    fn register_handler(&self, irq_handler_descriptor: exception::asynchronous::IRQHandlerDescriptor<Self::IRQNumberType>) -> Result<(), &'static str> {
+        self.handler_table.write(|table| {
+            let irq_number = irq_handler_descriptor.number().get();
+
+            if table[irq_number].is_some() {
+                return Err("IRQ handler already registered");
+            }
+
+            table[irq_number] = Some(irq_handler_descriptor);
+
+            Ok(())
+        })
+    }
+
+    /// enables the IRQ for the calling core only
+    fn enable(&self, irq: &Self::IRQNumberType) {
+        let core_id = cpu::smp::core_id::<usize>();
+
+        let (control_reg, bit) = match irq.get() {
+            irq @ 0..=3 => (&self.registers.CORE_TIMER_INTERRUPT_CONTROL[core_id], irq - Self::FIRST_TIMER_IRQ),
+            irq @ 4..=7 => (&self.registers.CORE_MAILBOX_INTERRUPT_CONTROL[core_id], irq - Self::FIRST_MAILBOX_IRQ),
+            irq => panic!("local IRQ {} cannot be enabled", irq),
+        };
+
+        control_reg.set(control_reg.get() | (1 << bit));
+    }
+
+    fn handle_pending_irqs<'irq_context>(&'irq_context self, _ic: &exception::asynchronous::IRQContext<'irq_context>) {
+        self.handler_table.read(|table| {
+            for irq_number in self.pending_irqs() {
+                // the handler may read the mailbox, the bits it saw are cleared once it returns
+                let mailbox = irq_number.checked_sub(Self::FIRST_MAILBOX_IRQ);
+                let mailbox_bits = mailbox.map(|mailbox| self.mailbox_pending(mailbox));
+
+                match table[irq_number] {
+                    None => panic!("no handler registered for local IRQ {}", irq_number),
+                    Some(descriptor) => {
+                        descriptor.handler().handle().expect("error handling IRQ");
+                    },
+                }
+
+                if let (Some(mailbox), Some(bits)) = (mailbox, mailbox_bits) {
+                    self.clear_mailbox(mailbox, bits);
+                }
+            }
+        })
+    }
+
+    fn print_handler(&self) {
+        use crate::info;
+
+        info!("    local handler:");
+
+        self.handler_table.read(|table| {
+            for (i, opt) in table.iter().enumerate() {
+                if let Some(handler) = opt {
+                    info!("        {: >3}. {}", i, handler.name());
+                }
+            }
+        });
+    }
+}
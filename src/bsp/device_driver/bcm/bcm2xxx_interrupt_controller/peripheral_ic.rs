@@ -1,6 +1,6 @@
 use super::{PendingIRQs, PeripheralIRQ};
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, exception, memory::{Address, Virtual}, synchronization::{interface::{Mutex, ReadWriteEx}, IRQSafeNullLock, InitStateLock}
+    bsp::device_driver::common::MMIODerefWrapper, exception, memory::{Address, Virtual}, synchronization::{interface::{Mutex, ReadWriteEx}, IRQSafeSpinLock, InitStateLock}
 };
 use alloc::vec::Vec;
 
@@ -36,7 +36,7 @@ type ReadOnlyRegisters = MMIODerefWrapper<RORegisterBlock>;
 type HandlerTable = Vec<Option<exception::asynchronous::IRQHandlerDescriptor<PeripheralIRQ>>>;
 
 pub struct PeripheralIC {
-    wo_registers: IRQSafeNullLock<WriteOnlyRegisters>,
+    wo_registers: IRQSafeSpinLock<WriteOnlyRegisters>,
     ro_registers: ReadOnlyRegisters,
     handler_table: InitStateLock<HandlerTable>,
 }
@@ -46,7 +46,7 @@ impl PeripheralIC {
     /// - the user must ensure to provide a correct MMIO start address
     pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
         Self {
-            wo_registers: IRQSafeNullLock::new(WriteOnlyRegisters::new(mmio_start_addr)),
+            wo_registers: IRQSafeSpinLock::new(WriteOnlyRegisters::new(mmio_start_addr)),
             ro_registers: ReadOnlyRegisters::new(mmio_start_addr),
             handler_table: InitStateLock::new(Vec::new()),
         }
@@ -0,0 +1,274 @@
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper, cpu, driver, exception::asynchronous::IRQNumber, memory::{self, Address, Virtual}, synchronization::{interface::Mutex, IRQSafeSpinLock}, time
+};
+
+use core::{marker::PhantomData, mem::size_of, time::Duration};
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_bitfields, register_structs, registers::{ReadOnly, WriteOnly},
+};
+
+pub mod property_tag;
+
+register_bitfields! {
+    u32,
+
+    // Mailbox Status, the ARM reads from mailbox 0 and writes to mailbox 1
+    STATUS [
+        FULL OFFSET(31) NUMBITS(1) [],
+        EMPTY OFFSET(30) NUMBITS(1) []
+    ],
+
+    // Mailbox Message, the upper 28 bits of a 16 byte aligned address plus the channel
+    MESSAGE [
+        DATA OFFSET(4) NUMBITS(28) [],
+        CHANNEL OFFSET(0) NUMBITS(4) [
+            PropertyArmToVc = 8
+        ]
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => READ: ReadOnly<u32, MESSAGE::Register>),
+        (0x04 => _reserved1),
+        (0x18 => STATUS0: ReadOnly<u32, STATUS::Register>),
+        (0x1C => _reserved2),
+        (0x20 => WRITE: WriteOnly<u32, MESSAGE::Register>),
+        (0x24 => _reserved3),
+        (0x38 => STATUS1: ReadOnly<u32, STATUS::Register>),
+        (0x3C => _reserved4),
+        (0x40 => @END),
+    }
+}
+
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+/// words in a property buffer, including the buffer header and the end tag
+const BUFFER_WORDS: usize = 256;
+
+/// buffer size, code and end tag
+const BUFFER_OVERHEAD_WORDS: usize = 3;
+
+/// tag id, value buffer size and request/response code
+const TAG_HEADER_WORDS: usize = 3;
+
+const CODE_REQUEST: u32 = 0x0000_0000;
+const CODE_RESPONSE_SUCCESS: u32 = 0x8000_0000;
+const CODE_RESPONSE_ERROR: u32 = 0x8000_0001;
+const CODE_TAG_RESPONSE: u32 = 1 << 31;
+
+/// the VideoCore sees ARM memory through this bus alias, which bypasses its L2 cache
+const BUS_ADDR_UNCACHED_ALIAS: usize = 0xC000_0000;
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// a property tag the firmware understands
+///
+/// # safety
+/// - `Request` and `Response` must be plain data that is valid for any bit pattern, the firmware
+///   writes the response over the request
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait Tag {
+    const ID: u32;
+
+    type Request: Copy;
+    type Response: Copy;
+}
+
+/// a property message under construction, holds any number of tags that are processed by the
+/// firmware in order
+pub struct Message {
+    words: [u32; BUFFER_WORDS - BUFFER_OVERHEAD_WORDS],
+    len: usize,
+}
+
+/// where the response to a tag pushed onto a `Message` can be found
+pub struct TagHandle<T: Tag> {
+    offset: usize,
+    tag: PhantomData<T>,
+}
+
+/// the buffer shared with the VideoCore, it covers whole cache lines so that cleaning and
+/// invalidating it can't affect anything else
+#[repr(C, align(64))]
+struct DmaBuffer([u32; BUFFER_WORDS]);
+
+struct MailboxInner {
+    registers: Registers,
+    buffer: DmaBuffer,
+}
+
+pub struct Mailbox {
+    inner: IRQSafeSpinLock<MailboxInner>,
+}
+
+impl Message {
+    pub const fn new() -> Self {
+        Self {
+            words: [0; BUFFER_WORDS - BUFFER_OVERHEAD_WORDS],
+            len: 0,
+        }
+    }
+
+    const fn value_words<T: Tag>() -> usize {
+        let request = size_of::<T::Request>();
+        let response = size_of::<T::Response>();
+
+        (if request > response { request } else { response }).div_ceil(size_of::<u32>())
+    }
+
+    /// append a tag, its response can be read with the returned handle once the message was sent
+    pub fn push<T: Tag>(&mut self, request: T::Request) -> Result<TagHandle<T>, &'static str> {
+        let value_words = Self::value_words::<T>();
+        let offset = self.len;
+
+        if offset + TAG_HEADER_WORDS + value_words > self.words.len() {
+            return Err("property message is full");
+        }
+
+        self.words[offset] = T::ID;
+        self.words[offset + 1] = (value_words * size_of::<u32>()) as u32;
+        self.words[offset + 2] = CODE_REQUEST;
+        self.words[offset + TAG_HEADER_WORDS..offset + TAG_HEADER_WORDS + value_words].fill(0);
+
+        unsafe {
+            core::ptr::write_unaligned(self.words.as_mut_ptr().add(offset + TAG_HEADER_WORDS) as *mut T::Request, request);
+        }
+
+        self.len += TAG_HEADER_WORDS + value_words;
+
+        Ok(TagHandle { offset, tag: PhantomData })
+    }
+
+    pub fn response<T: Tag>(&self, handle: &TagHandle<T>) -> Result<T::Response, &'static str> {
+        let code = self.words[handle.offset + 2];
+
+        if code & CODE_TAG_RESPONSE == 0 {
+            return Err("firmware did not process tag");
+        }
+
+        if ((code & !CODE_TAG_RESPONSE) as usize) < size_of::<T::Response>() {
+            return Err("firmware response is shorter than expected");
+        }
+
+        Ok(unsafe { core::ptr::read_unaligned(self.words.as_ptr().add(handle.offset + TAG_HEADER_WORDS) as *const T::Response) })
+    }
+}
+
+impl MailboxInner {
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            buffer: DmaBuffer([0; BUFFER_WORDS]),
+        }
+    }
+
+    fn virt_buffer_addr(&self) -> Address<Virtual> {
+        Address::new(self.buffer.0.as_ptr() as usize)
+    }
+
+    fn write(&self, data: u32) -> Result<(), &'static str> {
+        let deadline = time::time_manager().uptime() + RESPONSE_TIMEOUT;
+
+        while self.registers.STATUS1.matches_all(STATUS::FULL::SET) {
+            if time::time_manager().uptime() > deadline {
+                return Err("mailbox stays full");
+            }
+
+            cpu::nop();
+        }
+
+        self.registers.WRITE.write(MESSAGE::DATA.val(data >> 4) + MESSAGE::CHANNEL::PropertyArmToVc);
+
+        Ok(())
+    }
+
+    /// wait for the firmware to answer the message `data` on the property channel
+    fn read(&self, data: u32) -> Result<(), &'static str> {
+        let deadline = time::time_manager().uptime() + RESPONSE_TIMEOUT;
+
+        loop {
+            while self.registers.STATUS0.matches_all(STATUS::EMPTY::SET) {
+                if time::time_manager().uptime() > deadline {
+                    return Err("firmware did not answer");
+                }
+
+                cpu::nop();
+            }
+
+            let message = self.registers.READ.extract();
+
+            // anything else is an answer on another channel, which we never use
+            if message.matches_all(MESSAGE::CHANNEL::PropertyArmToVc) && message.read(MESSAGE::DATA) == data >> 4 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn call(&mut self, message: &mut Message) -> Result<(), &'static str> {
+        let len = BUFFER_OVERHEAD_WORDS + message.len;
+        let buffer = &mut self.buffer.0;
+
+        buffer[0] = (len * size_of::<u32>()) as u32;
+        buffer[1] = CODE_REQUEST;
+        buffer[2..2 + message.len].copy_from_slice(&message.words[..message.len]);
+        buffer[2 + message.len] = 0;
+
+        // the VideoCore isn't coherent with the ARM's caches
+        cpu::clean_invalidate_dcache_to_poc(self.virt_buffer_addr(), size_of::<DmaBuffer>());
+
+        let phys_addr = memory::mmu::try_kernel_virt_addr_to_phys_addr(self.virt_buffer_addr())?;
+        let data = (phys_addr.as_usize() | BUS_ADDR_UNCACHED_ALIAS) as u32;
+
+        self.write(data)?;
+        self.read(data)?;
+
+        // drop lines that were speculatively fetched while the firmware was writing
+        cpu::clean_invalidate_dcache_to_poc(self.virt_buffer_addr(), size_of::<DmaBuffer>());
+
+        let buffer = &self.buffer.0;
+        match buffer[1] {
+            CODE_RESPONSE_SUCCESS => (),
+            CODE_RESPONSE_ERROR => return Err("firmware failed to parse the property message"),
+            _ => return Err("firmware returned an invalid response code"),
+        }
+
+        message.words[..message.len].copy_from_slice(&buffer[2..2 + message.len]);
+
+        Ok(())
+    }
+}
+
+impl Mailbox {
+    pub const COMPATIBLE: &'static str = "BCM Mailbox";
+
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            inner: IRQSafeSpinLock::new(MailboxInner::new(mmio_start_addr))
+        }
+    }
+
+    /// send all tags of `message` to the firmware and wait for its responses
+    pub fn call(&self, message: &mut Message) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.call(message))
+    }
+
+    /// send a single tag
+    pub fn property<T: Tag>(&self, request: T::Request) -> Result<T::Response, &'static str> {
+        let mut message = Message::new();
+        let handle = message.push::<T>(request)?;
+
+        self.call(&mut message)?;
+        message.response(&handle)
+    }
+}
+
+impl driver::interface::DeviceDriver for Mailbox {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+}
@@ -0,0 +1,234 @@
+use super::Tag;
+
+/// a region of memory as reported by the firmware
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct MemoryRegion {
+    pub base: u32,
+    pub size: u32,
+}
+
+/// a clock and its rate in Hz
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct ClockRate {
+    pub clock_id: u32,
+    pub rate: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct SetClockRateRequest {
+    pub clock_id: u32,
+    pub rate: u32,
+    pub skip_setting_turbo: u32,
+}
+
+/// in pixels
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct Dimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// in pixels
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct Offset {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// a framebuffer allocated by the firmware, `base` is a bus address
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct FramebufferAllocation {
+    pub base: u32,
+    pub size: u32,
+}
+
+/// pixel orders understood by `SetPixelOrder`
+#[allow(unused)]
+pub mod pixel_order {
+    pub const BGR: u32 = 0x0;
+    pub const RGB: u32 = 0x1;
+}
+
+/// clock ids understood by the clock tags
+#[allow(unused)]
+pub mod clock_id {
+    pub const EMMC: u32 = 0x1;
+    pub const UART: u32 = 0x2;
+    pub const ARM: u32 = 0x3;
+    pub const CORE: u32 = 0x4;
+    pub const V3D: u32 = 0x5;
+    pub const H264: u32 = 0x6;
+    pub const ISP: u32 = 0x7;
+    pub const SDRAM: u32 = 0x8;
+    pub const PIXEL: u32 = 0x9;
+    pub const PWM: u32 = 0xA;
+    pub const EMMC2: u32 = 0xC;
+}
+
+pub struct GetFirmwareRevision;
+pub struct GetBoardModel;
+pub struct GetBoardRevision;
+pub struct GetBoardMacAddress;
+pub struct GetBoardSerial;
+pub struct GetArmMemory;
+pub struct GetVcMemory;
+pub struct GetClockRate;
+pub struct GetMaxClockRate;
+pub struct GetMinClockRate;
+pub struct SetClockRate;
+pub struct AllocateFramebuffer;
+pub struct ReleaseFramebuffer;
+pub struct SetPhysicalSize;
+pub struct SetVirtualSize;
+pub struct SetDepth;
+pub struct SetPixelOrder;
+pub struct GetPitch;
+pub struct SetVirtualOffset;
+
+unsafe impl Tag for GetFirmwareRevision {
+    const ID: u32 = 0x0000_0001;
+
+    type Request = ();
+    type Response = u32;
+}
+
+unsafe impl Tag for GetBoardModel {
+    const ID: u32 = 0x0001_0001;
+
+    type Request = ();
+    type Response = u32;
+}
+
+unsafe impl Tag for GetBoardRevision {
+    const ID: u32 = 0x0001_0002;
+
+    type Request = ();
+    type Response = u32;
+}
+
+unsafe impl Tag for GetBoardMacAddress {
+    const ID: u32 = 0x0001_0003;
+
+    type Request = ();
+    type Response = [u8; 6];
+}
+
+unsafe impl Tag for GetBoardSerial {
+    const ID: u32 = 0x0001_0004;
+
+    type Request = ();
+    type Response = u64;
+}
+
+unsafe impl Tag for GetArmMemory {
+    const ID: u32 = 0x0001_0005;
+
+    type Request = ();
+    type Response = MemoryRegion;
+}
+
+unsafe impl Tag for GetVcMemory {
+    const ID: u32 = 0x0001_0006;
+
+    type Request = ();
+    type Response = MemoryRegion;
+}
+
+/// takes a clock id
+unsafe impl Tag for GetClockRate {
+    const ID: u32 = 0x0003_0002;
+
+    type Request = u32;
+    type Response = ClockRate;
+}
+
+/// takes a clock id
+unsafe impl Tag for GetMaxClockRate {
+    const ID: u32 = 0x0003_0004;
+
+    type Request = u32;
+    type Response = ClockRate;
+}
+
+/// takes a clock id
+unsafe impl Tag for GetMinClockRate {
+    const ID: u32 = 0x0003_0007;
+
+    type Request = u32;
+    type Response = ClockRate;
+}
+
+unsafe impl Tag for SetClockRate {
+    const ID: u32 = 0x0003_8002;
+
+    type Request = SetClockRateRequest;
+    type Response = ClockRate;
+}
+
+/// takes the alignment of the buffer in bytes
+unsafe impl Tag for AllocateFramebuffer {
+    const ID: u32 = 0x0004_0001;
+
+    type Request = u32;
+    type Response = FramebufferAllocation;
+}
+
+unsafe impl Tag for ReleaseFramebuffer {
+    const ID: u32 = 0x0004_8001;
+
+    type Request = ();
+    type Response = ();
+}
+
+/// the size of the picture sent to the display
+unsafe impl Tag for SetPhysicalSize {
+    const ID: u32 = 0x0004_8003;
+
+    type Request = Dimensions;
+    type Response = Dimensions;
+}
+
+/// the size of the buffer, of which the display shows a window at the virtual offset
+unsafe impl Tag for SetVirtualSize {
+    const ID: u32 = 0x0004_8004;
+
+    type Request = Dimensions;
+    type Response = Dimensions;
+}
+
+/// takes the bits per pixel
+unsafe impl Tag for SetDepth {
+    const ID: u32 = 0x0004_8005;
+
+    type Request = u32;
+    type Response = u32;
+}
+
+/// takes a `pixel_order`
+unsafe impl Tag for SetPixelOrder {
+    const ID: u32 = 0x0004_8006;
+
+    type Request = u32;
+    type Response = u32;
+}
+
+/// bytes per line
+unsafe impl Tag for GetPitch {
+    const ID: u32 = 0x0004_0008;
+
+    type Request = ();
+    type Response = u32;
+}
+
+unsafe impl Tag for SetVirtualOffset {
+    const ID: u32 = 0x0004_8009;
+
+    type Request = Offset;
+    type Response = Offset;
+}
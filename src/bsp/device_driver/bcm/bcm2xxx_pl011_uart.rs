@@ -1,10 +1,10 @@
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, console, cpu, driver, exception::{self, asynchronous::IRQNumber}, memory::{Address, Virtual}, synchronization::{interface::Mutex, IRQSafeNullLock}
+    bsp::device_driver::common::MMIODerefWrapper, common::RingBuffer, console, cpu, driver, exception::{self, asynchronous::IRQNumber}, memory::{Address, Virtual}, sched, synchronization::{interface::Mutex, IRQSafeSpinLock}, time
 };
 
-use core::fmt;
+use core::{fmt, time::Duration};
 use tock_registers::{
-    interfaces::{Readable, Writeable},
+    interfaces::{ReadWriteable, Readable, Writeable},
     register_bitfields, register_structs, registers::{ReadOnly, ReadWrite, WriteOnly}
 };
 
@@ -63,6 +63,13 @@ register_bitfields! {
             OneHalf = 0b010,
             ThreeQuarters = 0b011,
             SevenEights = 0b100
+        ],
+        TXIFLSEL OFFSET(0) NUMBITS(3) [
+            OneEight = 0b000,
+            OneQuarter = 0b001,
+            OneHalf = 0b010,
+            ThreeQuarters = 0b011,
+            SevenEights = 0b100
         ]
     ],
 
@@ -73,6 +80,11 @@ register_bitfields! {
             Enabled = 1
         ],
 
+        TXIM OFFSET(5) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
         RXIM OFFSET(4) NUMBITS(1) [
             Disabled = 0,
             Enabled = 1
@@ -82,6 +94,7 @@ register_bitfields! {
     // Masked Interrut Status Register
     MIS [
         RTMIS OFFSET(6) NUMBITS(1) [],
+        TXMIS OFFSET(5) NUMBITS(1) [],
         RXMIS OFFSET(4) NUMBITS(1) []
     ],
 
@@ -113,28 +126,37 @@ register_structs! {
 
 type Registers = MMIODerefWrapper<RegisterBlock>;
 
-#[derive(PartialEq)]
-enum BlockingMode {
-    Blocking,
-    NonBlocking
-}
+/// characters waiting for room in the TX FIFO, drained by the TX interrupt
+const TX_QUEUE_SIZE: usize = 4096;
+
+/// bytes received by the RX interrupts that were not read yet, more are dropped. holds a whole
+/// chainload chunk, which the host sends in one go
+const RX_QUEUE_SIZE: usize = 2048;
 
 struct PL011UartInner {
     registers: Registers,
-    chars_written: usize,
-    chars_read: usize
+    tx_queue: RingBuffer<u8, TX_QUEUE_SIZE>,
+    rx_queue: RingBuffer<u8, RX_QUEUE_SIZE>,
+
+    /// the thread blocked in `read_byte()`, woken once the RX interrupt queued something
+    reader: Option<sched::ThreadId>,
+    bytes_written: usize,
+    bytes_read: usize
 }
 
 pub struct PL011Uart {
-    inner: IRQSafeNullLock<PL011UartInner>
+    inner: IRQSafeSpinLock<PL011UartInner>
 }
 
 impl PL011UartInner {
     pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
         Self {
             registers: Registers::new(mmio_start_addr),
-            chars_written: 0,
-            chars_read: 0
+            tx_queue: RingBuffer::new(),
+            rx_queue: RingBuffer::new(),
+            reader: None,
+            bytes_written: 0,
+            bytes_read: 0
         }
     }
 
@@ -153,61 +175,74 @@ impl PL011UartInner {
 
         self.registers.LCR_H.write(LCR_H::WLEN::EightBit + LCR_H::FEN::FifosEnabled);
 
-        self.registers.IFLS.write(IFLS::RXIFLSEL::OneEight);
+        self.registers.IFLS.write(IFLS::RXIFLSEL::OneEight + IFLS::TXIFLSEL::OneEight);
         self.registers.IMSC.write(IMSC::RXIM::Enabled + IMSC::RTIM::Enabled);
 
         self.registers.CR.write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled);
     }
 
-    fn write_char(&mut self, c: char) {
-        while self.registers.FR.matches_all(FR::TXFF::SET) {
-            cpu::nop();
+    /// move queued characters into the TX FIFO until either runs out
+    fn fill_tx_fifo(&mut self) {
+        while !self.registers.FR.matches_all(FR::TXFF::SET) {
+            match self.tx_queue.pop() {
+                None => break,
+                Some(byte) => self.registers.DR.set(byte as u32),
+            }
         }
+    }
+
+    /// only waits if both the TX FIFO and the queue are full
+    fn write_byte(&mut self, byte: u8) {
+        // queued bytes go first, or they would be overtaken
+        self.fill_tx_fifo();
 
-        self.registers.DR.set(c as u32);
-        self.chars_written += 1;
+        if self.tx_queue.is_empty() && !self.registers.FR.matches_all(FR::TXFF::SET) {
+            self.registers.DR.set(byte as u32);
+        } else {
+            while self.tx_queue.push(byte).is_err() {
+                cpu::nop();
+                self.fill_tx_fifo();
+            }
+
+            // the FIFO is full at this point, so draining it below the trigger level raises the
+            // TX interrupt
+            self.registers.IMSC.modify(IMSC::TXIM::Enabled);
+        }
+
+        self.bytes_written += 1;
     }
 
-    fn write_array(&mut self, a: &[char]) {
-        for c in a {
-            self.write_char(*c);
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_byte(*byte);
         }
     }
 
-    fn flush(&self) {
+    /// send everything queued without relying on the TX interrupt, which may be masked
+    fn flush(&mut self) {
+        while !self.tx_queue.is_empty() {
+            self.fill_tx_fifo();
+        }
+
         while self.registers.FR.matches_all(FR::BUSY::SET) {
             cpu::nop();
         }
     }
 
-    fn read_char_converting(&mut self, blocking_mode: BlockingMode) -> Option<char> {
+    fn read_byte(&mut self) -> Option<u8> {
         if self.registers.FR.matches_all(FR::RXFE::SET) {
-            if blocking_mode == BlockingMode::NonBlocking {
-                return None;
-            }
-
-            while self.registers.FR.matches_all(FR::RXFE::SET) {
-                cpu::nop();
-            }
+            return None;
         }
 
-        let mut ret = self.registers.DR.get() as u8 as char;
+        self.bytes_read += 1;
 
-        if ret == '\r' {
-            ret = '\n';
-        }
-
-        self.chars_read += 1;
-
-        Some(ret)
+        Some(self.registers.DR.get() as u8)
     }
 }
 
 impl fmt::Write for PL011UartInner {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            self.write_char(c);
-        }
+        self.write_bytes(s.as_bytes());
 
         Ok(())
     }
@@ -218,7 +253,7 @@ impl PL011Uart {
 
     pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
         Self {
-            inner: IRQSafeNullLock::new(PL011UartInner::new(mmio_start_addr))
+            inner: IRQSafeSpinLock::new(PL011UartInner::new(mmio_start_addr))
         }
     }
 }
@@ -249,12 +284,8 @@ impl driver::interface::DeviceDriver for PL011Uart {
 }
 
 impl console::interface::Write for PL011Uart {
-    fn write_char(&self, c: char) {
-        self.inner.lock(|inner| inner.write_char(c));
-    }
-
-    fn write_array(&self, a: &[char]) {
-        self.inner.lock(|inner| inner.write_array(a));
+    fn write_bytes(&self, bytes: &[u8]) {
+        self.inner.lock(|inner| inner.write_bytes(bytes));
     }
 
     fn write_fmt(&self, args: core::fmt::Arguments) -> fmt::Result {
@@ -268,21 +299,71 @@ impl console::interface::Write for PL011Uart {
 
 impl console::interface::Read for PL011Uart {
     fn read_char(&self) -> char {
-        self.inner.lock(|inner| inner.read_char_converting(BlockingMode::Blocking).expect("when using BlockingMode::Blocking, a character should always be read"))
+        match self.read_byte() {
+            b'\r' => '\n',
+            byte => byte as char,
+        }
+    }
+
+    /// blocks the thread until a byte arrives, the RX FIFO is checked as well in case IRQs are
+    /// masked
+    fn read_byte(&self) -> u8 {
+        self.read_byte_until(Duration::MAX).unwrap()
+    }
+
+    fn read_byte_until(&self, deadline: Duration) -> Option<u8> {
+        // the scheduler's lock must not be taken inside of ours, it logs while holding it
+        let id = sched::current_thread_id();
+
+        loop {
+            let now = time::time_manager().uptime();
+
+            let byte = self.inner.lock(|inner| {
+                let byte = inner.rx_queue.pop().or_else(|| inner.read_byte());
+
+                // registered under the lock, so the RX interrupt either queued the byte already or
+                // will wake us up
+                inner.reader = if byte.is_none() && now < deadline { id } else { None };
+
+                byte
+            });
+
+            if byte.is_some() || now >= deadline {
+                return byte;
+            }
+
+            if deadline == Duration::MAX {
+                sched::park();
+                continue;
+            }
+
+            let timeout = time::time_manager().set_timeout(deadline - now, move || {
+                if let Some(id) = id {
+                    sched::wake(id);
+                }
+            });
+
+            sched::park();
+            time::time_manager().cancel(timeout);
+        }
     }
 
     fn clear_rx(&self) {
-        while self.inner.lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking)).is_some() {}
+        self.inner.lock(|inner| {
+            inner.rx_queue.clear();
+
+            while inner.read_byte().is_some() {}
+        });
     }
 }
 
 impl console::interface::Statistics for PL011Uart {
-    fn chars_written(&self) -> usize {
-        self.inner.lock(|inner| inner.chars_written)
+    fn bytes_written(&self) -> usize {
+        self.inner.lock(|inner| inner.bytes_written)
     }
 
-    fn chars_read(&self) -> usize {
-        self.inner.lock(|inner| inner.chars_read)
+    fn bytes_read(&self) -> usize {
+        self.inner.lock(|inner| inner.bytes_read)
     }
 }
 
@@ -290,19 +371,36 @@ impl console::interface::All for PL011Uart {}
 
 impl exception::asynchronous::interface::IRQHandler for PL011Uart {
     fn handle(&self) -> Result<(), &'static str> {
-        self.inner.lock(|inner| {
+        let reader = self.inner.lock(|inner| {
             let pending = inner.registers.MIS.extract();
             
             inner.registers.ICR.write(ICR::ALL::CLEAR);
 
+            let mut reader = None;
+
             if pending.matches_any(MIS::RXMIS::SET + MIS::RTMIS::SET) {
-                // echo any received chars
-                while let Some(c) = inner.read_char_converting(BlockingMode::NonBlocking) {
-                    inner.write_char(c)
+                while let Some(byte) = inner.read_byte() {
+                    let _ = inner.rx_queue.push(byte);
+                }
+
+                reader = inner.reader.take();
+            }
+
+            if pending.matches_any(MIS::TXMIS::SET) {
+                inner.fill_tx_fifo();
+
+                if inner.tx_queue.is_empty() {
+                    inner.registers.IMSC.modify(IMSC::TXIM::Disabled);
                 }
             }
+
+            reader
         });
 
+        if let Some(id) = reader {
+            sched::wake(id);
+        }
+
         Ok(())
     }
 }
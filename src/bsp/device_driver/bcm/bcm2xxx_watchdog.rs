@@ -0,0 +1,91 @@
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper, cpu, driver, exception::asynchronous::IRQNumber, memory::{Address, Virtual}, synchronization::{interface::Mutex, IRQSafeSpinLock}
+};
+
+use tock_registers::{
+    interfaces::{ReadWriteable, Writeable},
+    register_bitfields, register_structs, registers::ReadWrite,
+};
+
+register_bitfields! {
+    u32,
+
+    // Power Management Reset Control
+    PM_RSTC [
+        PASSWORD OFFSET(24) NUMBITS(8) [
+            Value = 0x5A
+        ],
+        WRCFG OFFSET(4) NUMBITS(2) [
+            Clear = 0b00,
+            FullReset = 0b10
+        ]
+    ],
+
+    // Power Management Watchdog, counts down in ticks of 16 us
+    PM_WDOG [
+        PASSWORD OFFSET(24) NUMBITS(8) [
+            Value = 0x5A
+        ],
+        TIME OFFSET(0) NUMBITS(20) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => _reserved1),
+        (0x1C => PM_RSTC: ReadWrite<u32, PM_RSTC::Register>),
+        (0x20 => _reserved2),
+        (0x24 => PM_WDOG: ReadWrite<u32, PM_WDOG::Register>),
+        (0x28 => @END),
+    }
+}
+
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+struct WatchdogInner {
+    registers: Registers,
+}
+
+pub struct Watchdog {
+    inner: IRQSafeSpinLock<WatchdogInner>,
+}
+
+impl WatchdogInner {
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+        }
+    }
+
+    fn reset(&mut self) {
+        // expire after the shortest time the firmware's own reboot uses as well
+        self.registers.PM_WDOG.write(PM_WDOG::PASSWORD::Value + PM_WDOG::TIME.val(10));
+        self.registers.PM_RSTC.modify(PM_RSTC::PASSWORD::Value + PM_RSTC::WRCFG::FullReset);
+    }
+}
+
+impl Watchdog {
+    pub const COMPATIBLE: &'static str = "BCM Watchdog";
+
+    pub const unsafe fn new(mmio_start_addr: Address<Virtual>) -> Self {
+        Self {
+            inner: IRQSafeSpinLock::new(WatchdogInner::new(mmio_start_addr))
+        }
+    }
+
+    /// reset the whole board, the watchdog fires a few ticks later
+    pub fn reset(&self) -> ! {
+        self.inner.lock(|inner| inner.reset());
+
+        cpu::wait_forever()
+    }
+}
+
+impl driver::interface::DeviceDriver for Watchdog {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+}
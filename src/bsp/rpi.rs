@@ -3,6 +3,9 @@ pub mod driver;
 pub mod exception;
 pub mod memory;
 
+use super::device_driver::{property_tag::{clock_id, GetArmMemory, GetBoardModel, GetBoardRevision, GetBoardSerial, GetClockRate, GetFirmwareRevision, GetVcMemory}, Message};
+use crate::info;
+
 pub fn board_name() -> &'static str {
     #[cfg(feature = "bsp_rpi3")]
     { "Raspberry Pi 3" }
@@ -10,3 +13,32 @@ pub fn board_name() -> &'static str {
     #[cfg(feature = "bsp_rpi4")]
     { "Raspberry Pi 4" }
 }
+
+/// print what the firmware reports about the board
+pub fn print_board_info() -> Result<(), &'static str> {
+    let mut message = Message::new();
+    let firmware_revision = message.push::<GetFirmwareRevision>(())?;
+    let board_model = message.push::<GetBoardModel>(())?;
+    let board_revision = message.push::<GetBoardRevision>(())?;
+    let board_serial = message.push::<GetBoardSerial>(())?;
+    let arm_memory = message.push::<GetArmMemory>(())?;
+    let vc_memory = message.push::<GetVcMemory>(())?;
+    let arm_clock = message.push::<GetClockRate>(clock_id::ARM)?;
+    let uart_clock = message.push::<GetClockRate>(clock_id::UART)?;
+
+    driver::mailbox().call(&mut message)?;
+
+    let arm_memory = message.response(&arm_memory)?;
+    let vc_memory = message.response(&vc_memory)?;
+
+    info!("    firmware revision: {:#010x}", message.response(&firmware_revision)?);
+    info!("    board model:       {:#010x}", message.response(&board_model)?);
+    info!("    board revision:    {:#010x}", message.response(&board_revision)?);
+    info!("    board serial:      {:#018x}", message.response(&board_serial)?);
+    info!("    ARM memory:        {:#010x}, {} MiB", arm_memory.base, arm_memory.size >> 20);
+    info!("    VC memory:         {:#010x}, {} MiB", vc_memory.base, vc_memory.size >> 20);
+    info!("    ARM clock:         {} MHz", message.response(&arm_clock)?.rate / 1_000_000);
+    info!("    UART clock:        {} MHz", message.response(&uart_clock)?.rate / 1_000_000);
+
+    Ok(())
+}
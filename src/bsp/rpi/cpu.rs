@@ -1,3 +1,17 @@
+use crate::{cpu, memory::{Address, Physical}};
+
 #[no_mangle]
 #[link_section = ".text._start_arguments"]
 pub static BOOT_CORE_ID: u64 = 0;
+
+pub const NUM_CORES: usize = 4;
+
+/// release a secondary core parked by the firmware on the spin-table
+///
+/// # safety
+/// - `phys_entry_addr` must be the physical address of a valid entry point for secondary cores
+pub unsafe fn release_secondary_core(core_id: usize, phys_entry_addr: Address<Physical>) {
+    let virt_release_addr = super::memory::virt_spin_table_release_addr(core_id);
+
+    cpu::smp::spin_table_release(virt_release_addr, phys_entry_addr.as_usize() as u64);
+}
@@ -1,9 +1,26 @@
-use super::{exception, memory::map::mmio};
-use crate::{bsp::device_driver, console, driver as generic_driver, exception as generic_exception, memory::{self, mmu::MMIODescriptor}};
+use super::{exception, memory::{self as bsp_memory, map::mmio}};
+use crate::{bsp::device_driver, comet, console, driver as generic_driver, exception as generic_exception, framebuffer, log::LevelFilter, memory::{self, mmu::MMIODescriptor, Address}, time};
 use core::{mem::MaybeUninit, sync::atomic::{AtomicBool, Ordering}};
 
+const FRAMEBUFFER_WIDTH: usize = 1280;
+const FRAMEBUFFER_HEIGHT: usize = 720;
+const FRAMEBUFFER_DEPTH: usize = 32;
+
+/// the most verbose log records shown on the display
+const FRAMEBUFFER_CONSOLE_MAX_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// the most verbose log records written to the UART, as plain text or as Comet frames with the
+/// feature "comet_log"
+const UART_MAX_LEVEL: LevelFilter = LevelFilter::Trace;
+
 static mut PL011_UART: MaybeUninit<device_driver::PL011Uart> = MaybeUninit::uninit();
 static mut GPIO: MaybeUninit<device_driver::GPIO> = MaybeUninit::uninit();
+static mut WATCHDOG: MaybeUninit<device_driver::Watchdog> = MaybeUninit::uninit();
+static mut MAILBOX: MaybeUninit<device_driver::Mailbox> = MaybeUninit::uninit();
+static mut FRAMEBUFFER: MaybeUninit<device_driver::VideoCoreFramebuffer> = MaybeUninit::uninit();
+
+/// shows the console on the display next to the UART
+static FRAMEBUFFER_CONSOLE: console::FramebufferConsole = console::FramebufferConsole::new();
 
 #[cfg(feature = "bsp_rpi3")]
 static mut INTERRUPT_CONTROLLER: MaybeUninit<device_driver::InterruptController> = MaybeUninit::uninit();
@@ -21,8 +38,16 @@ unsafe fn instantiate_uart() -> Result<(), &'static str> {
 }
 
 unsafe fn post_init_uart() -> Result<(), &'static str> {
-    console::register_console(PL011_UART.assume_init_ref());
-    Ok(())
+    let uart = PL011_UART.assume_init_ref();
+
+    // log records either reach the host as Comet frames, which `comet debug` decodes, with plain
+    // text in between, or as plain text that any terminal shows
+    if cfg!(feature = "comet_log") {
+        console::register_console(uart, LevelFilter::Off)?;
+        console::register_log_output(comet::log_sink(), UART_MAX_LEVEL)
+    } else {
+        console::register_console(uart, UART_MAX_LEVEL)
+    }
 }
 
 unsafe fn instantiate_gpio() -> Result<(), &'static str> {
@@ -39,12 +64,64 @@ unsafe fn post_init_gpio() -> Result<(), &'static str> {
     Ok(())
 }
 
+unsafe fn instantiate_watchdog() -> Result<(), &'static str> {
+    let mmio_descriptor = MMIODescriptor::new(mmio::WATCHDOG_START, mmio::WATCHDOG_SIZE);
+    let virt_addr = memory::mmu::kernel_map_mmio(device_driver::Watchdog::COMPATIBLE, &mmio_descriptor)?;
+
+    WATCHDOG.write(device_driver::Watchdog::new(virt_addr));
+
+    Ok(())
+}
+
+unsafe fn instantiate_mailbox() -> Result<(), &'static str> {
+    let mmio_descriptor = MMIODescriptor::new(mmio::MAILBOX_START, mmio::MAILBOX_SIZE);
+    let virt_addr = memory::mmu::kernel_map_mmio(device_driver::Mailbox::COMPATIBLE, &mmio_descriptor)?;
+
+    MAILBOX.write(device_driver::Mailbox::new(virt_addr));
+
+    Ok(())
+}
+
+/// the frame allocator starts out with the DRAM that is safe to assume, the firmware knows how
+/// much the VideoCore actually took
+unsafe fn post_init_mailbox() -> Result<(), &'static str> {
+    let arm_memory = MAILBOX.assume_init_ref().property::<device_driver::property_tag::GetArmMemory>(())?;
+    let phys_arm_memory_end = Address::new(arm_memory.base as usize + arm_memory.size as usize);
+
+    if let Some((name, phys_region)) = bsp_memory::mmu::phys_firmware_dram_zone(phys_arm_memory_end) {
+        memory::mmu::kernel_add_frame_zone(name, phys_region);
+    }
+
+    Ok(())
+}
+
+/// needs the mailbox to be instantiated
+unsafe fn instantiate_framebuffer() -> Result<(), &'static str> {
+    FRAMEBUFFER.write(device_driver::VideoCoreFramebuffer::new(MAILBOX.assume_init_ref(), FRAMEBUFFER_WIDTH, FRAMEBUFFER_HEIGHT, FRAMEBUFFER_DEPTH));
+
+    Ok(())
+}
+
+unsafe fn post_init_framebuffer() -> Result<(), &'static str> {
+    if !FRAMEBUFFER.assume_init_ref().is_available() {
+        return Ok(());
+    }
+
+    framebuffer::register_framebuffer(FRAMEBUFFER.assume_init_ref());
+
+    FRAMEBUFFER_CONSOLE.init(FRAMEBUFFER.assume_init_ref())?;
+    console::register_console(&FRAMEBUFFER_CONSOLE, FRAMEBUFFER_CONSOLE_MAX_LEVEL)
+}
+
 #[cfg(feature = "bsp_rpi3")]
 unsafe fn instantiate_interrupt_controller() -> Result<(), &'static str> {
-    let mmio_descriptor = MMIODescriptor::new(mmio::PERIPHERAL_IC_START, mmio::PERIPHERAL_IC_SIZE);
-    let virt_addr = memory::mmu::kernel_map_mmio(device_driver::InterruptController::COMPATIBLE, &mmio_descriptor)?;
+    let periph_mmio_descriptor = MMIODescriptor::new(mmio::PERIPHERAL_IC_START, mmio::PERIPHERAL_IC_SIZE);
+    let periph_virt_addr = memory::mmu::kernel_map_mmio("BCM Peripheral IC", &periph_mmio_descriptor)?;
 
-    INTERRUPT_CONTROLLER.write(device_driver::InterruptController::new(virt_addr));
+    let local_mmio_descriptor = MMIODescriptor::new(mmio::LOCAL_IC_START, mmio::LOCAL_IC_SIZE);
+    let local_virt_addr = memory::mmu::kernel_map_mmio("BCM Local IC", &local_mmio_descriptor)?;
+
+    INTERRUPT_CONTROLLER.write(device_driver::InterruptController::new(local_virt_addr, periph_virt_addr));
 
     Ok(())
 }
@@ -86,6 +163,33 @@ unsafe fn init_driver_gpio() -> Result<(), &'static str> {
     Ok(())
 }
 
+unsafe fn init_driver_watchdog() -> Result<(), &'static str> {
+    instantiate_watchdog()?;
+
+    let watchdog_descriptor = generic_driver::DeviceDriverDescriptor::new(WATCHDOG.assume_init_ref(), None, None);
+    generic_driver::driver_manager().register_driver(watchdog_descriptor);
+
+    Ok(())
+}
+
+unsafe fn init_driver_mailbox() -> Result<(), &'static str> {
+    instantiate_mailbox()?;
+
+    let mailbox_descriptor = generic_driver::DeviceDriverDescriptor::new(MAILBOX.assume_init_ref(), Some(post_init_mailbox), None);
+    generic_driver::driver_manager().register_driver(mailbox_descriptor);
+
+    Ok(())
+}
+
+unsafe fn init_driver_framebuffer() -> Result<(), &'static str> {
+    instantiate_framebuffer()?;
+
+    let framebuffer_descriptor = generic_driver::DeviceDriverDescriptor::new(FRAMEBUFFER.assume_init_ref(), Some(post_init_framebuffer), None);
+    generic_driver::driver_manager().register_driver(framebuffer_descriptor);
+
+    Ok(())
+}
+
 unsafe fn init_driver_interrupt_controller() -> Result<(), &'static str> {
     instantiate_interrupt_controller()?;
 
@@ -95,6 +199,13 @@ unsafe fn init_driver_interrupt_controller() -> Result<(), &'static str> {
     Ok(())
 }
 
+unsafe fn init_driver_arch_timer() -> Result<(), &'static str> {
+    let arch_timer_descriptor = generic_driver::DeviceDriverDescriptor::new(time::time_manager(), None, Some(exception::asynchronous::irq_map::ARCH_TIMER));
+    generic_driver::driver_manager().register_driver(arch_timer_descriptor);
+
+    Ok(())
+}
+
 pub unsafe fn init() -> Result<(), &'static str> {
     static INIT_DONE: AtomicBool = AtomicBool::new(false);
     if INIT_DONE.load(Ordering::Relaxed) {
@@ -103,8 +214,27 @@ pub unsafe fn init() -> Result<(), &'static str> {
 
     init_driver_uart()?;
     init_driver_gpio()?;
+    init_driver_watchdog()?;
+    init_driver_mailbox()?;
+    init_driver_framebuffer()?;
     init_driver_interrupt_controller()?;
+    init_driver_arch_timer()?;
 
     INIT_DONE.store(true, Ordering::Relaxed);
     Ok(())
 }
+
+/// reset the board through the watchdog
+pub fn reboot() -> ! {
+    unsafe { WATCHDOG.assume_init_ref().reset() }
+}
+
+/// the firmware's property interface
+pub fn mailbox() -> &'static device_driver::Mailbox {
+    unsafe { MAILBOX.assume_init_ref() }
+}
+
+/// the console on the display, which stays blank on boards without one
+pub fn framebuffer_console() -> &'static console::FramebufferConsole {
+    &FRAMEBUFFER_CONSOLE
+}
@@ -4,8 +4,10 @@ pub use bsp::device_driver::IRQNumber;
 
 #[cfg(feature = "bsp_rpi3")]
 pub(in crate::bsp) mod irq_map {
-    use super::bsp::device_driver::{IRQNumber, PeripheralIRQ};
+    use super::bsp::device_driver::{IRQNumber, LocalIRQ, PeripheralIRQ};
 
+    /// CNTPNSIRQ, the non-secure physical timer of the local core
+    pub const ARCH_TIMER: IRQNumber = IRQNumber::Local(LocalIRQ::new(1));
     pub const PL011_UART: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(57));
 }
 
@@ -13,5 +15,7 @@ pub(in crate::bsp) mod irq_map {
 pub(in crate::bsp) mod irq_map {
     use super::bsp::device_driver::IRQNumber;
 
+    /// PPI 14, the non-secure physical timer of the local core
+    pub const ARCH_TIMER: IRQNumber = IRQNumber::new(30);
     pub const PL011_UART: IRQNumber = IRQNumber::new(57);
 }
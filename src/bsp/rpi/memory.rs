@@ -7,6 +7,12 @@ extern "Rust" {
     static __code_start: UnsafeCell<()>;
     static __code_end_exclusive: UnsafeCell<()>;
 
+    static __exception_table_start: UnsafeCell<()>;
+    static __exception_table_end_exclusive: UnsafeCell<()>;
+
+    static __kernel_symbols_start: UnsafeCell<()>;
+    static __kernel_symbols_end_exclusive: UnsafeCell<()>;
+
     static __data_start: UnsafeCell<()>;
     static __data_end_exclusive: UnsafeCell<()>;
 
@@ -18,11 +24,23 @@ extern "Rust" {
 
     static __boot_core_stack_start: UnsafeCell<()>;
     static __boot_core_stack_end_exclusive: UnsafeCell<()>;
+
+    static __secondary_core_stacks_start: UnsafeCell<()>;
+    static __secondary_core_stacks_end_exclusive: UnsafeCell<()>;
+
+    static __dynamic_map_start: UnsafeCell<()>;
+    static __dynamic_map_end_exclusive: UnsafeCell<()>;
 }
 
 pub(super) mod map {
     use super::*;
 
+    /// release addresses the firmware parks the cores on, one `u64` per core
+    pub const SPIN_TABLE_START: Address<Physical> = Address::new(0xD8);
+
+    /// where the firmware loads the kernel image, must match `__rpi_phys_binary_load_addr`
+    pub const BINARY_LOAD_ADDR: Address<Physical> = Address::new(0x8_0000);
+
     #[cfg(feature = "bsp_rpi3")]
     pub mod mmio {
         use super::*;
@@ -30,12 +48,21 @@ pub(super) mod map {
         pub const PERIPHERAL_IC_START: Address<Physical> = Address::new(0x3F00_B200);
         pub const PERIPHERAL_IC_SIZE: usize = 0x24;
 
+        pub const MAILBOX_START: Address<Physical> = Address::new(0x3F00_B880);
+        pub const MAILBOX_SIZE: usize = 0x40;
+
+        pub const WATCHDOG_START: Address<Physical> = Address::new(0x3F10_0000);
+        pub const WATCHDOG_SIZE: usize = 0x28;
+
         pub const GPIO_START: Address<Physical> = Address::new(0x3F20_0000);
         pub const GPIO_SIZE: usize = 0xA0;
         
         pub const PL011_UART_START: Address<Physical> = Address::new(0x3F20_1000);
         pub const PL011_UART_SIZE: usize = 0x48;
 
+        pub const LOCAL_IC_START: Address<Physical> = Address::new(0x4000_0000);
+        pub const LOCAL_IC_SIZE: usize = 0x100;
+
         pub const END: Address<Physical> = Address::new(0x4001_0000);
     }
 
@@ -43,6 +70,12 @@ pub(super) mod map {
     pub mod mmio {
         use super::*;
 
+        pub const MAILBOX_START: Address<Physical> = Address::new(0xFE00_B880);
+        pub const MAILBOX_SIZE: usize = 0x40;
+
+        pub const WATCHDOG_START: Address<Physical> = Address::new(0xFE10_0000);
+        pub const WATCHDOG_SIZE: usize = 0x28;
+
         pub const GPIO_START: Address<Physical> = Address::new(0xFE20_0000);
         pub const GPIO_SIZE: usize = 0xA0;
 
@@ -58,6 +91,11 @@ pub(super) mod map {
         pub const END: Address<Physical> = Address::new(0xFF85_0000);
     }
 
+    /// DRAM that is left to the ARM cores before the firmware can be asked. the VideoCore takes
+    /// `gpu_mem` from the top of the first GiB, 64 MiB by default on the Raspberry Pi 3 and 76 MiB
+    /// on the Raspberry Pi 4, so this leaves room for up to 256 MiB
+    pub const SAFE_DRAM_END: Address<Physical> = Address::new(0x3000_0000);
+
     pub const END: Address<Physical> = mmio::END;
 }
 
@@ -127,6 +165,86 @@ fn boot_core_stack_size() -> usize {
     unsafe { (__boot_core_stack_end_exclusive.get() as usize) - (__boot_core_stack_start.get() as usize) }
 }
 
+/// # safety
+/// - value is provided by linker script and must be trusted as-is
+fn secondary_core_stack_stride() -> usize {
+    let size = unsafe { (__secondary_core_stacks_end_exclusive.get() as usize) - (__secondary_core_stacks_start.get() as usize) };
+
+    size / (super::cpu::NUM_CORES - 1)
+}
+
+/// every secondary core's stack is preceded by a guard page, see `kernel.ld`
+///
+/// # safety
+/// - value is provided by linker script and must be trusted as-is
+#[inline(always)]
+fn virt_secondary_core_stack_start(core_id: usize) -> PageAddress<Virtual> {
+    assert!(core_id > 0 && core_id < super::cpu::NUM_CORES);
+
+    let start = unsafe { __secondary_core_stacks_start.get() as usize };
+
+    PageAddress::from(start + (core_id - 1) * secondary_core_stack_stride() + mmu::KernelGranule::SIZE)
+}
+
+fn secondary_core_stack_size() -> usize {
+    secondary_core_stack_stride() - mmu::KernelGranule::SIZE
+}
+
+/// # safety
+/// - value is provided by linker script and must be trusted as-is
+#[inline(always)]
+fn virt_dynamic_map_start() -> PageAddress<Virtual> {
+    PageAddress::from(unsafe { __dynamic_map_start.get() as usize })
+}
+
+/// # safety
+/// - value is provided by linker script and must be trusted as-is
+fn dynamic_map_size() -> usize {
+    unsafe { (__dynamic_map_end_exclusive.get() as usize) - (__dynamic_map_start.get() as usize) }
+}
+
+/// the raw exception table collected by the linker
+///
+/// # safety
+/// - value is provided by linker script and must be trusted as-is
+pub fn exception_table() -> &'static [u8] {
+    unsafe {
+        let start = __exception_table_start.get() as *const u8;
+        let size = (__exception_table_end_exclusive.get() as usize) - (start as usize);
+
+        core::slice::from_raw_parts(start, size)
+    }
+}
+
+/// the raw symbol table patched into the image after linking
+///
+/// # safety
+/// - value is provided by linker script and must be trusted as-is
+pub fn kernel_symbol_table() -> &'static [u8] {
+    unsafe {
+        let start = __kernel_symbols_start.get() as *const u8;
+        let size = (__kernel_symbols_end_exclusive.get() as usize) - (start as usize);
+
+        core::slice::from_raw_parts(start, size)
+    }
+}
+
+/// the spin-table lives in the first page of DRAM, which is mapped as part of the boot core's stack
+#[inline(always)]
+pub fn virt_spin_table_release_addr(core_id: usize) -> Address<Virtual> {
+    virt_boot_core_stack_start().into_inner() + map::SPIN_TABLE_START.as_usize() + core_id * core::mem::size_of::<u64>()
+}
+
+#[inline(always)]
+pub fn phys_spin_table_release_addr(core_id: usize) -> Address<Physical> {
+    map::SPIN_TABLE_START + core_id * core::mem::size_of::<u64>()
+}
+
+#[inline(always)]
+pub fn phys_binary_load_addr() -> Address<Physical> {
+    map::BINARY_LOAD_ADDR
+}
+
 #[inline(always)]
 pub fn phys_addr_space_end_exclusive_addr() -> PageAddress<Physical> {
     PageAddress::from(map::END)
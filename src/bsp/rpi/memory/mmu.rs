@@ -1,13 +1,16 @@
-use crate::{memory::{mmu as generic_mmu, mmu::*, Virtual, Physical}, synchronization::InitStateLock};
+use crate::{memory::{mmu as generic_mmu, mmu::*, Address, Virtual, Physical}, synchronization::RwLock};
 
 type KernelTranslationTable = <KernelVirtAddrSpace as AssociatedTranslationTable>::TableStartFromTop;
 
 pub type KernelGranule = TranslationGranule<{ 64 * 1024 }>;
 pub type KernelVirtAddrSpace = AddressSpace<{ kernel_virt_addr_space_size() }>;
 
+pub type UserVirtAddrSpace = AddressSpace<{ 1024 * 1024 * 1024 }>;
+pub type UserTranslationTable = <UserVirtAddrSpace as AssociatedTranslationTable>::TableStartFromBottom;
+
 #[link_section = ".data"]
 #[no_mangle]
-static KERNEL_TABLES: InitStateLock<KernelTranslationTable> = InitStateLock::new(KernelTranslationTable::new_for_precompute());
+static KERNEL_TABLES: RwLock<KernelTranslationTable> = RwLock::new(KernelTranslationTable::new_for_precompute());
 
 // this willbe patched to the correct value by the translation table tool after linking.
 // the given value below is just a placeholder
@@ -15,6 +18,9 @@ static KERNEL_TABLES: InitStateLock<KernelTranslationTable> = InitStateLock::new
 #[no_mangle]
 static PHYS_KERNEL_TABLES_BASE_ADDR: u64 = 0xC0FFEE33C0FFEE33;
 
+const BOOT_CORE_STACK_NAME: &str = "Kernel boot-core stack";
+const SECONDARY_CORE_STACK_NAMES: [&str; 3] = ["Kernel core 1 stack", "Kernel core 2 stack", "Kernel core 3 stack"];
+
 const fn kernel_virt_addr_space_size() -> usize {
     let __kernel_virt_addr_space_size;
 
@@ -66,6 +72,15 @@ fn virt_boot_core_stack_region() -> MemoryRegion<Virtual> {
     MemoryRegion::new(start_page_addr, end_exclusive_page_addr)
 }
 
+fn virt_secondary_core_stack_region(core_id: usize) -> MemoryRegion<Virtual> {
+    let num_pages = size_to_num_pages(super::secondary_core_stack_size());
+
+    let start_page_addr = super::virt_secondary_core_stack_start(core_id);
+    let end_exclusive_page_addr = start_page_addr.checked_offset(num_pages as isize).unwrap();
+
+    MemoryRegion::new(start_page_addr, end_exclusive_page_addr)
+}
+
 fn kernel_virt_to_phys_region(virt_region: MemoryRegion<Virtual>) -> MemoryRegion<Physical> {
     let phys_start_page_addr = generic_mmu::try_kernel_virt_page_addr_to_phys_page_addr(virt_region.start_page_addr()).unwrap();
     let phys_end_exclusive_page_addr = phys_start_page_addr.checked_offset(virt_region.num_pages() as isize).unwrap();
@@ -77,7 +92,7 @@ fn kernel_page_attributes(virt_page_addr: PageAddress<Virtual>) -> AttributeFiel
     generic_mmu::try_kernel_page_attributes(virt_page_addr).unwrap()
 }
 
-pub fn kernel_translation_tables() -> &'static InitStateLock<KernelTranslationTable> {
+pub fn kernel_translation_tables() -> &'static RwLock<KernelTranslationTable> {
     &KERNEL_TABLES
 }
 
@@ -90,6 +105,60 @@ pub fn virt_mmio_remap_region() -> MemoryRegion<Virtual> {
     MemoryRegion::new(start_page_addr, end_exclusive_page_addr)
 }
 
+/// every region a kernel stack can live in, which includes the heap for the stacks of threads
+pub fn virt_stack_holding_regions() -> impl Iterator<Item = MemoryRegion<Virtual>> {
+    [virt_heap_region(), virt_boot_core_stack_region()]
+        .into_iter()
+        .chain((1..super::super::cpu::NUM_CORES).map(virt_secondary_core_stack_region))
+}
+
+/// the unmapped page `kernel.ld` leaves below a stack, which an overflow of the stack faults on
+fn virt_guard_page_below(virt_stack_region: MemoryRegion<Virtual>) -> MemoryRegion<Virtual> {
+    let start_page_addr = virt_stack_region.start_page_addr().checked_offset(-1).unwrap();
+
+    MemoryRegion::new(start_page_addr, virt_stack_region.start_page_addr())
+}
+
+/// the guard page of every stack set up by `kernel.ld`, along with the name of the stack
+pub fn virt_stack_guard_regions() -> impl Iterator<Item = (&'static str, MemoryRegion<Virtual>)> {
+    [(BOOT_CORE_STACK_NAME, virt_boot_core_stack_region())]
+        .into_iter()
+        .chain((1..super::super::cpu::NUM_CORES).map(|core_id| (SECONDARY_CORE_STACK_NAMES[core_id - 1], virt_secondary_core_stack_region(core_id))))
+        .map(|(name, virt_stack_region)| (name, virt_guard_page_below(virt_stack_region)))
+}
+
+/// DRAM behind everything the kernel image occupies, up to where the VideoCore's share may start
+pub fn phys_free_dram_zones() -> [(&'static str, MemoryRegion<Physical>); 1] {
+    let kernel_regions = [virt_code_region(), virt_data_region(), virt_heap_region(), virt_boot_core_stack_region()]
+        .into_iter()
+        .chain((1..super::super::cpu::NUM_CORES).map(virt_secondary_core_stack_region));
+
+    let phys_kernel_end_exclusive = kernel_regions
+        .map(|virt_region| kernel_virt_to_phys_region(virt_region).end_exclusive_page_addr())
+        .max_by_key(|page_addr| page_addr.into_inner())
+        .unwrap();
+
+    [("DRAM", MemoryRegion::new(phys_kernel_end_exclusive, PageAddress::from(super::map::SAFE_DRAM_END)))]
+}
+
+/// the DRAM between the zones of `phys_free_dram_zones()` and `phys_arm_memory_end`, which the
+/// firmware reports as the end of the ARM's share
+pub fn phys_firmware_dram_zone(phys_arm_memory_end: Address<Physical>) -> Option<(&'static str, MemoryRegion<Physical>)> {
+    let start = super::map::SAFE_DRAM_END;
+    let end = phys_arm_memory_end.align_down_page();
+
+    (end.as_usize() > start.as_usize()).then(|| ("DRAM reported by firmware", MemoryRegion::new(PageAddress::from(start), PageAddress::from(end))))
+}
+
+pub fn virt_dynamic_map_region() -> MemoryRegion<Virtual> {
+    let num_pages = size_to_num_pages(super::dynamic_map_size());
+
+    let start_page_addr = super::virt_dynamic_map_start();
+    let end_exclusive_page_addr = start_page_addr.checked_offset(num_pages as isize).unwrap();
+
+    MemoryRegion::new(start_page_addr, end_exclusive_page_addr)
+}
+
 pub fn kernel_add_mapping_records_for_precomputed() {
     let virt_code_region = virt_code_region();
     generic_mmu::kernel_add_mapping_record(
@@ -117,9 +186,19 @@ pub fn kernel_add_mapping_records_for_precomputed() {
 
     let virt_boot_core_stack_region = virt_boot_core_stack_region();
     generic_mmu::kernel_add_mapping_record(
-        "Kernel boot-core stack",
+        BOOT_CORE_STACK_NAME,
         &virt_boot_core_stack_region,
         &kernel_virt_to_phys_region(virt_boot_core_stack_region),
         &kernel_page_attributes(virt_boot_core_stack_region.start_page_addr()),
     );
+
+    for (core_id, name) in (1..).zip(SECONDARY_CORE_STACK_NAMES) {
+        let virt_secondary_core_stack_region = virt_secondary_core_stack_region(core_id);
+        generic_mmu::kernel_add_mapping_record(
+            name,
+            &virt_secondary_core_stack_region,
+            &kernel_virt_to_phys_region(virt_secondary_core_stack_region),
+            &kernel_page_attributes(virt_secondary_core_stack_region.start_page_addr()),
+        );
+    }
 }
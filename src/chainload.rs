@@ -0,0 +1,256 @@
+use core::{convert::Infallible, num::NonZeroUsize, time::Duration};
+
+use crate::{bsp::{self, memory::mmu::KernelGranule}, comet::{self, Command, Decoder, Kind}, console, cpu, exception, info, memory::{self, mmu::{AccessPermissions, AttributeFields, MemAttributes, MemoryRegion}, Address, Physical, Virtual}, sched, time, warn};
+
+#[cfg(target_arch = "aarch64")]
+#[path = "arch/aarch64/chainload.rs"]
+mod arch_chainload;
+
+/// largest image the host may announce
+const MAX_IMAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// largest `SendBinary` payload the host may send, a whole frame must fit the UART's RX queue
+const MAX_CHUNK_SIZE: usize = 1024;
+
+const WORD_SIZE: usize = core::mem::size_of::<u64>();
+
+/// how long the host may take to announce the binary
+const ANNOUNCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// how long the host may go without sending a chunk
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// frames holding the received image, followed by a copy of the trampoline and one parked flag
+/// per core
+struct LoadBuffer {
+    phys_region: MemoryRegion<Physical>,
+    virt_region: MemoryRegion<Virtual>,
+    image_size: usize,
+}
+
+impl LoadBuffer {
+    fn new(image_size: usize) -> Result<Self, &'static str> {
+        let size = Self::trampoline_offset(image_size) + arch_chainload::trampoline().len() + bsp::cpu::NUM_CORES * WORD_SIZE;
+        let num_pages = NonZeroUsize::new(size.div_ceil(KernelGranule::SIZE)).unwrap();
+
+        let phys_region = memory::mmu::kernel_alloc_frames(num_pages)?;
+        let attr = AttributeFields {
+            mem_attributes: MemAttributes::CacheableDRAM,
+            access_permissions: AccessPermissions::ReadWrite,
+            execute_never: true,
+            user_accessible: false,
+        };
+
+        let virt_region = match unsafe { memory::mmu::kernel_map("chainload buffer", &phys_region, &attr) } {
+            Err(x) => {
+                let _ = memory::mmu::kernel_free_frames(&phys_region);
+                return Err(x);
+            }
+            Ok(region) => region,
+        };
+
+        Ok(Self { phys_region, virt_region, image_size })
+    }
+
+    /// the image is copied in whole words, so the trampoline starts behind it rounded up
+    const fn trampoline_offset(image_size: usize) -> usize {
+        image_size.next_multiple_of(WORD_SIZE)
+    }
+
+    fn parked_flags_offset(&self) -> usize {
+        Self::trampoline_offset(self.image_size) + arch_chainload::trampoline().len()
+    }
+
+    fn image(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.virt_region.start_addr().as_usize() as *mut u8, self.image_size) }
+    }
+
+    fn phys_addr(&self, offset: usize) -> Address<Physical> {
+        self.phys_region.start_addr() + offset
+    }
+
+    fn phys_trampoline_addr(&self) -> Address<Physical> {
+        self.phys_addr(Self::trampoline_offset(self.image_size))
+    }
+
+    fn phys_parked_flag_addr(&self, core_id: usize) -> Address<Physical> {
+        self.phys_addr(self.parked_flags_offset() + core_id * WORD_SIZE)
+    }
+
+    /// put the trampoline and the parked flags in place and make them visible to the cores running
+    /// with caches off
+    fn prepare_trampoline(&mut self) {
+        let trampoline = arch_chainload::trampoline();
+        let trampoline_offset = Self::trampoline_offset(self.image_size);
+        let base = self.virt_region.start_addr().as_usize() as *mut u8;
+
+        unsafe {
+            core::ptr::copy_nonoverlapping(trampoline.as_ptr(), base.add(trampoline_offset), trampoline.len());
+
+            // the boot core doesn't park, it is waiting for the others
+            let flags = base.add(self.parked_flags_offset()) as *mut u64;
+            for core_id in 0..bsp::cpu::NUM_CORES {
+                core::ptr::write_volatile(flags.add(core_id), (core_id as u64 == bsp::cpu::BOOT_CORE_ID) as u64);
+            }
+        }
+
+        cpu::clean_invalidate_dcache_to_poc(self.virt_region.start_addr() + trampoline_offset, trampoline.len() + bsp::cpu::NUM_CORES * WORD_SIZE);
+    }
+}
+
+impl Drop for LoadBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(x) = memory::mmu::kernel_unmap("chainload buffer", &self.virt_region) {
+                warn!("cannot unmap chainload buffer: {}", x);
+                return;
+            }
+        }
+
+        if let Err(x) = memory::mmu::kernel_free_frames(&self.phys_region) {
+            warn!("cannot free chainload buffer: {}", x);
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// wait for the reply to our `RequestBinary`, returns the announced image size and CRC-32
+fn receive_image_info(decoder: &mut Decoder, sequence: u16) -> Result<(usize, u32), &'static str> {
+    let deadline = time::time_manager().uptime() + ANNOUNCE_TIMEOUT;
+
+    loop {
+        let Ok(frame) = comet::receive_frame(decoder, deadline).ok_or("timed out waiting for the binary announcement")? else {
+            continue;
+        };
+
+        if frame.kind != Kind::Reply || frame.command != Command::RequestBinary as u8 || frame.sequence != sequence {
+            continue;
+        }
+
+        let (Some(size), Some(crc)) = (read_u32(frame.payload, 0), read_u32(frame.payload, 4)) else {
+            return Err("malformed binary announcement");
+        };
+
+        return Ok((size as usize, crc));
+    }
+}
+
+/// receive `SendBinary` chunks, each carrying its offset into the image, until the image is
+/// complete. every chunk is answered with the offset we expect next, so the host resends anything
+/// that got lost or arrived corrupted
+fn receive_image(decoder: &mut Decoder, image: &mut [u8]) -> Result<(), &'static str> {
+    let mut received = 0;
+    let mut deadline = time::time_manager().uptime() + CHUNK_TIMEOUT;
+
+    while received < image.len() {
+        let Ok(frame) = comet::receive_frame(decoder, deadline).ok_or("timed out waiting for the binary")? else {
+            continue;
+        };
+
+        if frame.kind != Kind::Command || frame.command != Command::SendBinary as u8 {
+            continue;
+        }
+
+        deadline = time::time_manager().uptime() + CHUNK_TIMEOUT;
+
+        let sequence = frame.sequence;
+        let data = frame.payload.get(4..).unwrap_or_default();
+
+        if read_u32(frame.payload, 0) == Some(received as u32) && data.len() <= image.len() - received {
+            image[received..received + data.len()].copy_from_slice(data);
+            received += data.len();
+        }
+
+        let _ = comet::send_reply(sequence, Command::SendBinary, &(received as u32).to_le_bytes());
+    }
+
+    Ok(())
+}
+
+fn receive() -> Result<LoadBuffer, &'static str> {
+    let mut decoder = Decoder::new();
+
+    let sequence = comet::request_binary(MAX_CHUNK_SIZE)?;
+    let (size, crc) = receive_image_info(&mut decoder, sequence)?;
+
+    if size == 0 {
+        return Err("host has no binary to send");
+    }
+
+    if size > MAX_IMAGE_SIZE {
+        return Err("binary exceeds maximum image size");
+    }
+
+    let mut buffer = LoadBuffer::new(size)?;
+    receive_image(&mut decoder, buffer.image())?;
+
+    if comet::crc32(buffer.image()) != crc {
+        return Err("binary checksum mismatch");
+    }
+
+    Ok(buffer)
+}
+
+/// park all secondary cores and hand the boot core over to the image
+///
+/// # safety
+/// - `buffer` must hold a complete image
+unsafe fn boot(mut buffer: LoadBuffer) -> ! {
+    buffer.prepare_trampoline();
+
+    // parked cores wait for the new kernel to release them through the spin-table
+    for core_id in (0..bsp::cpu::NUM_CORES).filter(|&id| id as u64 != bsp::cpu::BOOT_CORE_ID) {
+        let virt_release_addr = bsp::memory::virt_spin_table_release_addr(core_id);
+
+        core::ptr::write_volatile(virt_release_addr.as_usize() as *mut u64, 0);
+        cpu::clean_invalidate_dcache_to_poc(virt_release_addr, WORD_SIZE);
+    }
+
+    info!("booting {} byte image at {}", buffer.image_size, bsp::memory::phys_binary_load_addr());
+    console::console().flush();
+
+    exception::asynchronous::local_irq_mask();
+
+    let phys_trampoline_addr = buffer.phys_trampoline_addr();
+
+    for core_id in (0..bsp::cpu::NUM_CORES).filter(|&id| id as u64 != bsp::cpu::BOOT_CORE_ID) {
+        let phys_parked_flag_addr = buffer.phys_parked_flag_addr(core_id);
+
+        sched::spawn_on(core_id, "chainload park", move || {
+            exception::asynchronous::local_irq_mask();
+
+            unsafe { arch_chainload::park(phys_trampoline_addr, bsp::memory::phys_spin_table_release_addr(core_id), phys_parked_flag_addr) }
+        });
+    }
+
+    arch_chainload::boot(
+        phys_trampoline_addr,
+        buffer.phys_addr(0),
+        bsp::memory::phys_binary_load_addr(),
+        LoadBuffer::trampoline_offset(buffer.image_size),
+        buffer.phys_parked_flag_addr(0),
+        bsp::cpu::NUM_CORES
+    )
+}
+
+/// request an image from the host over Comet and boot it in place of the running kernel. only
+/// returns if something went wrong, which is reported to the host as well
+///
+/// the parked secondary cores run from behind the image until the new kernel released them, so it
+/// must not allocate that memory before starting its secondary cores
+pub fn chainload() -> Result<Infallible, &'static str> {
+    if cpu::smp::num_cores_online() != bsp::cpu::NUM_CORES {
+        return Err("cannot park cores that never came online");
+    }
+
+    match receive() {
+        Err(x) => {
+            comet::report_error(x);
+            Err(x)
+        }
+        Ok(buffer) => unsafe { boot(buffer) },
+    }
+}
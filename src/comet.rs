@@ -1,45 +1,130 @@
-use crate::print;
+use crate::{common::TruncatingWriter, console, log, synchronization::{interface::Mutex, IRQSafeSpinLock}};
+use alloc::vec;
+use core::{fmt::Write, sync::atomic::{AtomicU16, Ordering}, time::Duration};
 
-const COMMAND_PREFIX: &str = "#COMET:";
+pub use comet_frame::{crc32, Decoder, DecodeError, Frame, Kind};
 
 #[allow(unused)]
 #[derive(Copy, Clone, Debug)]
 pub enum Command {
     SetDevice = 0x01,
     RequestBinary = 0x02,
-    SendBinary = 0x03
+    SendBinary = 0x03,
+    Error = 0x04
 }
 
-impl Command {
-    pub fn as_char(&self) -> char {
-        *self as u8 as char
-    }
+#[allow(unused)]
+pub enum Device {
+    Starship = 1,
+    Starlight = 2,
+    StarlightMini = 3
+}
+
+/// sequence number of the next frame we send
+static SEQUENCE: AtomicU16 = AtomicU16::new(0);
+
+/// log frames are put together here instead of on the heap, since records are also logged from
+/// within the heap allocator and while panicking
+struct LogFrameBuffers {
+    payload: [u8; comet_frame::MAX_PAYLOAD_LEN],
+    frame: [u8; comet_frame::MAX_FRAME_LEN],
+}
+
+/// sends every record as a `Log` frame, with the level character followed by the record's line as
+/// payload
+struct LogRecordSink {
+    buffers: IRQSafeSpinLock<LogFrameBuffers>,
 }
 
-fn send_command(command: Command, args: Option<&[u8]>) {
-    print!("{}", COMMAND_PREFIX);
-    print!("{}", command.as_char());
+static LOG_RECORD_SINK: LogRecordSink = LogRecordSink {
+    buffers: IRQSafeSpinLock::new(LogFrameBuffers {
+        payload: [0; comet_frame::MAX_PAYLOAD_LEN],
+        frame: [0; comet_frame::MAX_FRAME_LEN],
+    }),
+};
+
+/// encode a frame and write it with a single call, so it can't be interleaved with other output
+fn send(frame: &Frame) -> Result<(), &'static str> {
+    let mut buf = vec![0; frame.encoded_len()];
+
+    frame.encode(&mut buf).map_err(|_| "payload exceeds maximum frame size")?;
+    console::input_console().write_bytes(&buf);
+
+    Ok(())
+}
+
+fn next_sequence() -> u16 {
+    SEQUENCE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// send a command, returns the sequence number its reply will carry
+pub fn send_command(command: Command, payload: &[u8]) -> Result<u16, &'static str> {
+    let sequence = next_sequence();
+
+    send(&Frame { kind: Kind::Command, command: command as u8, sequence, payload })?;
+
+    Ok(sequence)
+}
 
-    if let Some(args) = args {
-        for arg in args {
-            print!("{}", *arg as char);
+/// answer the command with sequence number `sequence`
+pub fn send_reply(sequence: u16, command: Command, payload: &[u8]) -> Result<(), &'static str> {
+    send(&Frame { kind: Kind::Reply, command: command as u8, sequence, payload })
+}
+
+/// block until a complete frame arrives on the input console, frames that fail to decode are
+/// returned as errors so the caller can decide whether to retry. `None` once the uptime reaches
+/// `deadline`
+pub fn receive_frame(decoder: &mut Decoder, deadline: Duration) -> Option<Result<Frame<'_>, DecodeError>> {
+    let console = console::input_console();
+
+    loop {
+        // borrowck rejects returning the frame `push` borrows from inside the loop, so it's fetched
+        // again afterwards
+        if let Some(result) = decoder.push(console.read_byte_until(deadline)?) {
+            if let Err(x) = result {
+                return Some(Err(x));
+            }
+            break;
         }
     }
 
+    Some(Ok(decoder.frame().unwrap()))
 }
 
-#[allow(unused)]
-pub enum Device {
-    Starship = 1,
-    Starlight = 2,
-    StarlightMini = 3
+pub fn set_device(device: Device) {
+    let _ = send_command(Command::SetDevice, &[device as u8]);
 }
 
-pub fn set_device(device: Device) {
-    send_command(Command::SetDevice, Some(&[device as u8]))
+/// ask the host for a binary, announcing the largest `SendBinary` payload we accept
+pub fn request_binary(max_chunk_size: usize) -> Result<u16, &'static str> {
+    send_command(Command::RequestBinary, &(max_chunk_size as u32).to_le_bytes())
 }
 
-#[allow(unused)]
-pub fn request_binary() {
-    send_command(Command::RequestBinary, None)
+/// tell the host why a command failed
+pub fn report_error(message: &str) {
+    let _ = send_command(Command::Error, message.as_bytes());
+}
+
+impl log::interface::Sink for LogRecordSink {
+    fn log(&self, record: &log::Record) {
+        let timestamp = record.timestamp();
+
+        self.buffers.lock(|buffers| {
+            let mut writer = TruncatingWriter::new(&mut buffers.payload);
+
+            // records longer than a frame are cut short rather than dropped
+            let _ = write!(writer, "{}{}.{:06} {}: {}", record.level().as_char(), timestamp.as_secs(), timestamp.subsec_micros(), record.module_path(), record.args());
+
+            let frame = Frame { kind: Kind::Log, command: 0, sequence: next_sequence(), payload: writer.written() };
+
+            if let Ok(len) = frame.encode(&mut buffers.frame) {
+                console::input_console().write_bytes(&buffers.frame[..len]);
+            }
+        })
+    }
+}
+
+/// sends log records to the host as `Log` frames, instead of as text between the frames
+pub fn log_sink() -> &'static (dyn log::interface::Sink + Sync) {
+    &LOG_RECORD_SINK
 }
@@ -1,3 +1,9 @@
+mod ring_buffer;
+mod truncating_writer;
+
+pub use ring_buffer::RingBuffer;
+pub use truncating_writer::TruncatingWriter;
+
 #[inline(always)]
 pub const fn is_aligned(value: usize, alignment: usize) -> bool {
     assert!(alignment.is_power_of_two());
@@ -0,0 +1,67 @@
+use core::mem::MaybeUninit;
+
+/// a fixed capacity FIFO queue that never allocates, so it can be filled from IRQ context
+pub struct RingBuffer<T: Copy, const N: usize> {
+    items: [MaybeUninit<T>; N],
+    start: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            items: [const { MaybeUninit::uninit() }; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    #[allow(unused)]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[allow(unused)]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// append `item`, handing it back if the buffer is full
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+
+        self.items[(self.start + self.len) % N].write(item);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        // every slot between start and start + len has been written
+        let item = unsafe { self.items[self.start].assume_init() };
+
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+
+        Some(item)
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+}
@@ -1,35 +1,78 @@
-mod buffer_console;
+mod escape_parser;
+mod framebuffer_console;
+mod null_console;
+mod psf;
 
-use crate::{comet::{self, Device}, synchronization};
+use core::{fmt, time::Duration};
+
+use crate::{comet::{self, Device}, log::{self, LevelFilter}, synchronization::{interface::ReadWriteEx, InitStateLock}};
+
+pub use escape_parser::{ControlSequence, EscapeParser, Parsed};
+pub use framebuffer_console::FramebufferConsole;
 
 #[allow(unused)]
 pub mod interface {
-    use core::fmt;
+    use core::{fmt, time::Duration};
 
     pub trait Write {
-        fn write_char(&self, c: char);
-        fn write_array(&self, a: &[char]);
-        fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result;
+        /// write raw bytes, text is always UTF-8 encoded
+        fn write_bytes(&self, bytes: &[u8]);
+
+        fn write_str(&self, s: &str) {
+            self.write_bytes(s.as_bytes());
+        }
+
+        fn write_char(&self, c: char) {
+            self.write_str(c.encode_utf8(&mut [0; 4]));
+        }
+
+        /// consoles that can interleave with other cores should format under a single lock
+        fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+            fmt::write(&mut FmtAdapter(self), args)
+        }
+
         #[allow(unused)]
         fn flush(&self);
     }
 
+    /// writes formatted text to a console piece by piece
+    struct FmtAdapter<'a, T: ?Sized>(&'a T);
+
+    impl<T: Write + ?Sized> fmt::Write for FmtAdapter<'_, T> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.write_str(s);
+
+            Ok(())
+        }
+    }
+
     pub trait Read {
         fn read_char(&self) -> char {
             ' '
         }
 
+        /// read a raw byte, without any of the conversions `read_char` does
+        fn read_byte(&self) -> u8 {
+            self.read_char() as u8
+        }
+
+        /// like `read_byte`, but gives up once the uptime reaches `deadline`. consoles whose reads
+        /// block have to override this
+        fn read_byte_until(&self, deadline: Duration) -> Option<u8> {
+            Some(self.read_byte())
+        }
+
         fn clear_rx(&self);
     }
 
     pub trait Statistics {
         #[allow(unused)]
-        fn chars_written(&self) -> usize {
+        fn bytes_written(&self) -> usize {
             0
         }
 
         #[allow(unused)]
-        fn chars_read(&self) -> usize {
+        fn bytes_read(&self) -> usize {
             0
         }
     }
@@ -37,25 +80,205 @@ pub mod interface {
     pub trait All: Write + Read + Statistics {}
 }
 
-static CUR_CONSOLE: InitStateLock<&'static (dyn interface::All + Sync)> = InitStateLock::new(&buffer_console::BUFFER_CONSOLE);
+/// the most outputs that can be registered, including the kernel log
+const MAX_OUTPUTS: usize = 8;
 
-use synchronization::{interface::ReadWriteEx, InitStateLock};
+#[derive(Copy, Clone)]
+enum Target {
+    /// gets prints as they are and log records as lines of text
+    Console(&'static (dyn interface::All + Sync)),
 
-pub fn register_console(new_console: &'static (dyn interface::All + Sync)) {
-    CUR_CONSOLE.write(|con| *con = new_console);
+    /// only gets log records, like the kernel log or Comet
+    Log(&'static (dyn log::interface::Sink + Sync)),
+}
+
+/// somewhere output is sent to
+#[derive(Copy, Clone)]
+struct Output {
+    target: Target,
+
+    /// the most verbose log records the output gets, prints reach every console regardless
+    max_level: LevelFilter,
+}
+
+/// sends output to every registered output, input comes from just one console
+pub struct ConsoleManager {
+    /// a fixed array, since registering must not allocate, the heap allocator logs to the outputs
+    outputs: InitStateLock<[Option<Output>; MAX_OUTPUTS]>,
+    input: InitStateLock<&'static (dyn interface::All + Sync)>,
+}
+
+static CONSOLE_MANAGER: ConsoleManager = ConsoleManager::new();
+
+impl Output {
+    fn console(&self) -> Option<&'static (dyn interface::All + Sync)> {
+        match self.target {
+            Target::Console(console) => Some(console),
+            Target::Log(_) => None,
+        }
+    }
+}
+
+impl log::interface::Sink for Output {
+    fn log(&self, record: &log::Record) {
+        if !self.max_level.allows(record.level()) {
+            return;
+        }
+
+        match self.target {
+            Target::Console(console) => log::print_record(console, record),
+            Target::Log(sink) => sink.log(record),
+        }
+    }
+}
+
+impl ConsoleManager {
+    pub const fn new() -> Self {
+        let mut outputs = [None; MAX_OUTPUTS];
+
+        // the kernel log records from the very start, whether there is a console or not
+        outputs[0] = Some(Output {
+            target: Target::Log(&log::dmesg::DmesgSink),
+            max_level: LevelFilter::Trace,
+        });
+
+        Self {
+            outputs: InitStateLock::new(outputs),
+            input: InitStateLock::new(&null_console::NullConsole),
+        }
+    }
+
+    fn for_each_output(&self, f: impl FnMut(&Output)) {
+        self.outputs.read(|outputs| outputs.iter().flatten().for_each(f))
+    }
+
+    fn for_each_console(&self, mut f: impl FnMut(&'static (dyn interface::All + Sync))) {
+        self.for_each_output(|output| {
+            if let Some(console) = output.console() {
+                f(console);
+            }
+        })
+    }
+
+    fn input(&self) -> &'static (dyn interface::All + Sync) {
+        self.input.read(|input| *input)
+    }
+
+    /// hand `record` to every output that lets its level through
+    pub fn log(&self, record: &log::Record) {
+        self.for_each_output(|output| log::interface::Sink::log(output, record));
+    }
+}
+
+impl interface::Write for ConsoleManager {
+    fn write_bytes(&self, bytes: &[u8]) {
+        self.for_each_console(|console| console.write_bytes(bytes));
+    }
+
+    /// every console formats on its own, so that its output isn't interleaved with other cores'
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        let mut result = Ok(());
+        self.for_each_console(|console| result = result.and(console.write_fmt(args)));
+
+        result
+    }
+
+    fn flush(&self) {
+        self.for_each_console(|console| console.flush());
+    }
+}
+
+impl interface::Read for ConsoleManager {
+    fn read_char(&self) -> char {
+        self.input().read_char()
+    }
+
+    fn read_byte(&self) -> u8 {
+        self.input().read_byte()
+    }
+
+    fn read_byte_until(&self, deadline: Duration) -> Option<u8> {
+        self.input().read_byte_until(deadline)
+    }
+
+    fn clear_rx(&self) {
+        self.input().clear_rx();
+    }
+}
+
+impl interface::Statistics for ConsoleManager {
+    fn bytes_written(&self) -> usize {
+        let mut bytes_written = 0;
+        self.for_each_console(|console| bytes_written += console.bytes_written());
+
+        bytes_written
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.input().bytes_read()
+    }
+}
+
+impl interface::All for ConsoleManager {}
+
+pub fn console_manager() -> &'static ConsoleManager {
+    &CONSOLE_MANAGER
+}
+
+fn add_output(output: Output) -> Result<(), &'static str> {
+    CONSOLE_MANAGER.outputs.write(|outputs| {
+        let slot = outputs.iter_mut().find(|slot| slot.is_none()).ok_or("no free output slot")?;
+        *slot = Some(output);
+
+        Ok(())
+    })
+}
+
+/// send output to `new_console` as well, with log records up to `max_level`, the first console
+/// registered is also where input comes from
+///
+/// the kernel log so far is replayed to the new console, so that it doesn't miss the boot messages
+pub fn register_console(new_console: &'static (dyn interface::All + Sync), max_level: LevelFilter) -> Result<(), &'static str> {
+    let output = Output { target: Target::Console(new_console), max_level };
+
+    add_output(output)?;
 
     static FIRST_SWITCH: InitStateLock<bool> = InitStateLock::new(true);
     FIRST_SWITCH.write(|first| {
         if *first {
             *first = false;
 
+            set_input(new_console);
             comet::set_device(Device::Starlight);
-
-            buffer_console::BUFFER_CONSOLE.dump();
         }
-    })
+    });
+
+    log::dmesg::replay(&output);
+
+    Ok(())
 }
 
+/// send log records up to `max_level` to `sink` as well, starting with the kernel log so far
+pub fn register_log_output(sink: &'static (dyn log::interface::Sink + Sync), max_level: LevelFilter) -> Result<(), &'static str> {
+    let output = Output { target: Target::Log(sink), max_level };
+
+    add_output(output)?;
+    log::dmesg::replay(&output);
+
+    Ok(())
+}
+
+/// read input from `new_input`, which doesn't need to be registered for output
+pub fn set_input(new_input: &'static (dyn interface::All + Sync)) {
+    CONSOLE_MANAGER.input.write(|input| *input = new_input);
+}
+
+/// every registered console
 pub fn console() -> &'static dyn interface::All {
-    CUR_CONSOLE.read(|con| *con)
+    &CONSOLE_MANAGER
+}
+
+/// the console input comes from, which is also the one Comet talks over
+pub fn input_console() -> &'static dyn interface::All {
+    CONSOLE_MANAGER.input()
 }
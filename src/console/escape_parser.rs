@@ -0,0 +1,100 @@
+const ESCAPE: char = '\x1b';
+
+/// further parameters of a control sequence are dropped
+const MAX_PARAMS: usize = 8;
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    None,
+    Escape,
+
+    /// inside a control sequence, which ends with a byte in the range `0x40..=0x7e`
+    ControlSequence,
+}
+
+/// a complete control sequence, `ESC [` followed by parameters separated by `;` and a final
+/// character, like `ESC [ 1 ; 31 m`
+#[derive(Copy, Clone)]
+pub struct ControlSequence {
+    params: [u16; MAX_PARAMS],
+    num_params: usize,
+    final_char: char,
+}
+
+/// what a character turned out to be
+pub enum Parsed {
+    Char(char),
+    ControlSequence(ControlSequence),
+}
+
+/// splits a stream of characters into plain characters and control sequences, other escape
+/// sequences are dropped
+pub struct EscapeParser {
+    state: State,
+    sequence: ControlSequence,
+}
+
+impl ControlSequence {
+    const fn new() -> Self {
+        Self {
+            params: [0; MAX_PARAMS],
+            num_params: 1,
+            final_char: '\0',
+        }
+    }
+
+    /// empty parameters are 0
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.num_params.min(MAX_PARAMS)]
+    }
+
+    pub const fn final_char(&self) -> char {
+        self.final_char
+    }
+}
+
+impl EscapeParser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::None,
+            sequence: ControlSequence::new(),
+        }
+    }
+
+    /// feed the next character, returns `None` while inside of an escape sequence
+    pub fn feed(&mut self, c: char) -> Option<Parsed> {
+        match self.state {
+            State::None if c == ESCAPE => {
+                self.state = State::Escape;
+                None
+            }
+            State::None => Some(Parsed::Char(c)),
+            State::Escape => {
+                self.state = if c == '[' { State::ControlSequence } else { State::None };
+                self.sequence = ControlSequence::new();
+                None
+            }
+            State::ControlSequence => {
+                let sequence = &mut self.sequence;
+
+                match c {
+                    '0'..='9' => {
+                        if let Some(param) = sequence.params.get_mut(sequence.num_params - 1) {
+                            *param = param.saturating_mul(10).saturating_add(c as u16 - '0' as u16);
+                        }
+                    }
+                    ';' => sequence.num_params += 1,
+                    '\x40'..='\x7e' => {
+                        sequence.final_char = c;
+                        self.state = State::None;
+
+                        return Some(Parsed::ControlSequence(*sequence));
+                    }
+                    _ => (),
+                }
+
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,532 @@
+use alloc::{vec, vec::Vec};
+use core::{fmt, sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use super::{interface, psf::Font, ControlSequence, EscapeParser, Parsed};
+use crate::{framebuffer::{interface::Framebuffer, Color, Surface}, sched, synchronization::{interface::Mutex, IRQSafeSpinLock, SpinLock}};
+
+/// the public domain misc-fixed 8x13 font of X11, with the glyphs of ISO 8859-1 at their code
+/// points
+static FONT: &[u8] = include_bytes!("font.psf");
+
+const TAB_WIDTH: usize = 8;
+
+/// how often what was written is shown, at most
+const FRAME_PERIOD: Duration = Duration::from_millis(20);
+
+/// drawn for characters the font has no glyph for
+const REPLACEMENT_CHAR: char = '?';
+
+const BACKSPACE: char = '\x08';
+
+/// the VGA text mode colors in ANSI order, the bright variants follow the normal ones
+const PALETTE: [Color; 16] = [
+    Color::new(0x00, 0x00, 0x00),
+    Color::new(0xAA, 0x00, 0x00),
+    Color::new(0x00, 0xAA, 0x00),
+    Color::new(0xAA, 0x55, 0x00),
+    Color::new(0x00, 0x00, 0xAA),
+    Color::new(0xAA, 0x00, 0xAA),
+    Color::new(0x00, 0xAA, 0xAA),
+    Color::new(0xAA, 0xAA, 0xAA),
+    Color::new(0x55, 0x55, 0x55),
+    Color::new(0xFF, 0x55, 0x55),
+    Color::new(0x55, 0xFF, 0x55),
+    Color::new(0xFF, 0xFF, 0x55),
+    Color::new(0x55, 0x55, 0xFF),
+    Color::new(0xFF, 0x55, 0xFF),
+    Color::new(0x55, 0xFF, 0xFF),
+    Color::new(0xFF, 0xFF, 0xFF),
+];
+
+const DEFAULT_FG: u8 = 7;
+const DEFAULT_BG: u8 = 0;
+
+/// colors below this have a bright variant, used for bold text
+const NUM_NORMAL_COLORS: u8 = 8;
+
+#[derive(Copy, Clone)]
+struct Cell {
+    c: char,
+
+    /// indices into `PALETTE`
+    fg: u8,
+    bg: u8,
+}
+
+/// what a buffer shows, so that only the rows changed since have to be drawn
+#[derive(Copy, Clone)]
+struct BufferState {
+    /// `Screen::version` when the buffer was drawn
+    version: u64,
+
+    /// `Screen::scrolled` when the buffer was drawn
+    scrolled: u64,
+}
+
+/// the text on the console, along with what is needed to tell which rows changed
+struct Screen {
+    columns: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+
+    /// the version each row last changed at, which moves along with the row when scrolling
+    row_versions: Vec<u64>,
+    version: u64,
+
+    /// lines scrolled since the console was set up
+    scrolled: u64,
+
+    cursor_x: usize,
+    cursor_y: usize,
+}
+
+/// the framebuffer side, only used by the thread that presents
+struct Display {
+    framebuffer: &'static (dyn Framebuffer + Sync),
+    font: Font<'static>,
+
+    /// a copy of the screen as of the last frame, so drawing doesn't hold up writes
+    screen: Screen,
+
+    /// one per buffer of the framebuffer, `None` while what the buffer shows is unknown
+    buffers: Vec<Option<BufferState>>,
+
+    /// frames are skipped while something else uses the framebuffer
+    suspended: bool,
+}
+
+struct FramebufferConsoleInner {
+    screen: Screen,
+    fg: u8,
+    bg: u8,
+    bold: bool,
+
+    escape_parser: EscapeParser,
+
+    /// the start of a UTF-8 encoded character whose remaining bytes are still to be written
+    utf8: [u8; 4],
+    utf8_len: usize,
+
+    bytes_written: usize,
+}
+
+/// a text console on the display, drawn with an embedded PSF font
+///
+/// understands newlines, carriage returns, tabs, backspaces and the SGR sequences for colors
+/// (`ESC [ ... m`), everything else is drawn or ignored, there is no input
+///
+/// writes only change the text, a thread draws it on the framebuffer once per frame and `flush()`
+/// draws it right away
+pub struct FramebufferConsole {
+    inner: IRQSafeSpinLock<Option<FramebufferConsoleInner>>,
+    display: SpinLock<Option<Display>>,
+
+    /// whether the text changed or the buffers were invalidated since the last frame
+    dirty: AtomicBool,
+}
+
+impl Cell {
+    const fn blank(bg: u8) -> Self {
+        Self { c: ' ', fg: DEFAULT_FG, bg }
+    }
+}
+
+impl Screen {
+    fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            rows,
+            cells: vec![Cell::blank(DEFAULT_BG); columns * rows],
+            row_versions: vec![0; rows],
+            version: 0,
+            scrolled: 0,
+            cursor_x: 0,
+            cursor_y: 0,
+        }
+    }
+
+    /// mark `row` as changed
+    fn touch(&mut self, row: usize) {
+        self.version += 1;
+        self.row_versions[row] = self.version;
+    }
+
+    /// bring this copy up to date with `screen`, scrolling it and copying only the rows changed
+    /// since
+    fn update_from(&mut self, screen: &Screen) {
+        let lines = screen.scrolled - self.scrolled;
+
+        if lines < self.rows as u64 {
+            // the rows scrolled in are newer than this copy, so they are copied below
+            self.cells.copy_within(lines as usize * self.columns.., 0);
+
+            for y in 0..self.rows {
+                if screen.row_versions[y] > self.version {
+                    let row = y * self.columns..(y + 1) * self.columns;
+                    self.cells[row.clone()].copy_from_slice(&screen.cells[row]);
+                }
+            }
+        } else {
+            self.cells.copy_from_slice(&screen.cells);
+        }
+
+        self.row_versions.copy_from_slice(&screen.row_versions);
+        self.version = screen.version;
+        self.scrolled = screen.scrolled;
+        self.cursor_x = screen.cursor_x;
+        self.cursor_y = screen.cursor_y;
+    }
+}
+
+impl FramebufferConsoleInner {
+    fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            screen: Screen::new(columns, rows),
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            escape_parser: EscapeParser::new(),
+            utf8: [0; 4],
+            utf8_len: 0,
+            bytes_written: 0,
+        }
+    }
+
+    fn move_cursor(&mut self, x: usize, y: usize) {
+        let screen = &mut self.screen;
+
+        screen.touch(screen.cursor_y);
+
+        screen.cursor_x = x;
+        screen.cursor_y = y;
+
+        screen.touch(screen.cursor_y);
+    }
+
+    fn scroll_up(&mut self) {
+        let screen = &mut self.screen;
+
+        screen.cells.copy_within(screen.columns.., 0);
+
+        let last_row = (screen.rows - 1) * screen.columns;
+        screen.cells[last_row..].fill(Cell::blank(self.bg));
+
+        screen.row_versions.rotate_left(1);
+        screen.scrolled += 1;
+        screen.touch(screen.rows - 1);
+    }
+
+    fn new_line(&mut self) {
+        let (cursor_y, rows) = (self.screen.cursor_y, self.screen.rows);
+
+        if cursor_y + 1 < rows {
+            self.move_cursor(0, cursor_y + 1);
+            return;
+        }
+
+        // the cursor leaves its row as it scrolls up
+        self.screen.touch(cursor_y);
+        self.scroll_up();
+        self.move_cursor(0, cursor_y);
+    }
+
+    fn put_char(&mut self, c: char) {
+        let fg = if self.bold && self.fg < NUM_NORMAL_COLORS { self.fg + NUM_NORMAL_COLORS } else { self.fg };
+        let (x, y, columns) = (self.screen.cursor_x, self.screen.cursor_y, self.screen.columns);
+
+        self.screen.cells[y * columns + x] = Cell { c, fg, bg: self.bg };
+
+        if x + 1 < columns {
+            self.move_cursor(x + 1, y);
+        } else {
+            self.new_line();
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, sequence: &ControlSequence) {
+        for param in sequence.params().iter().copied() {
+            match param {
+                0 => {
+                    self.fg = DEFAULT_FG;
+                    self.bg = DEFAULT_BG;
+                    self.bold = false;
+                }
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.fg = (param - 30) as u8,
+                39 => self.fg = DEFAULT_FG,
+                40..=47 => self.bg = (param - 40) as u8,
+                49 => self.bg = DEFAULT_BG,
+                90..=97 => self.fg = (param - 90) as u8 + NUM_NORMAL_COLORS,
+                100..=107 => self.bg = (param - 100) as u8 + NUM_NORMAL_COLORS,
+                _ => (),
+            }
+        }
+    }
+
+    fn write_char(&mut self, c: char) {
+        let c = match self.escape_parser.feed(c) {
+            None => return,
+            Some(Parsed::ControlSequence(sequence)) => {
+                if sequence.final_char() == 'm' {
+                    self.select_graphic_rendition(&sequence);
+                }
+                return;
+            }
+            Some(Parsed::Char(c)) => c,
+        };
+
+        let (x, y) = (self.screen.cursor_x, self.screen.cursor_y);
+
+        match c {
+            '\n' => self.new_line(),
+            '\r' => self.move_cursor(0, y),
+            '\t' => self.move_cursor((x / TAB_WIDTH + 1).saturating_mul(TAB_WIDTH).min(self.screen.columns - 1), y),
+            BACKSPACE => self.move_cursor(x.saturating_sub(1), y),
+            c if c.is_control() => (),
+            c => self.put_char(c),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.bytes_written += 1;
+
+        // a new character drops the start of one that never completed
+        if byte & 0xC0 != 0x80 {
+            self.utf8_len = 0;
+        }
+
+        self.utf8[self.utf8_len] = byte;
+        self.utf8_len += 1;
+
+        // holds at most one character, since any character resets it
+        let c = match core::str::from_utf8(&self.utf8[..self.utf8_len]) {
+            Ok(s) => s.chars().next(),
+            Err(x) if x.error_len().is_some() => Some(char::REPLACEMENT_CHARACTER),
+            Err(_) => None,
+        };
+
+        if let Some(c) = c {
+            self.utf8_len = 0;
+            self.write_char(c);
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_byte(*byte);
+        }
+    }
+}
+
+impl Display {
+    fn draw_cell(&self, surface: &mut Surface, x: usize, y: usize) {
+        let screen = &self.screen;
+        let cell = screen.cells[y * screen.columns + x];
+        let (fg, bg) = if (x, y) == (screen.cursor_x, screen.cursor_y) { (cell.bg, cell.fg) } else { (cell.fg, cell.bg) };
+        let glyph = self.font.glyph(cell.c).or_else(|| self.font.glyph(REPLACEMENT_CHAR));
+
+        let (width, height) = (self.font.width(), self.font.height());
+
+        for glyph_y in 0..height {
+            for glyph_x in 0..width {
+                let color = if glyph.is_some_and(|glyph| glyph.is_set(glyph_x, glyph_y)) { fg } else { bg };
+
+                surface.put_pixel(x * width + glyph_x, y * height + glyph_y, PALETTE[color as usize]);
+            }
+        }
+    }
+
+    /// bring `surface` up to date, scrolling what it shows and drawing only the rows changed since
+    fn draw(&mut self, surface: &mut Surface) {
+        let (font_height, rows, columns) = (self.font.height(), self.screen.rows, self.screen.columns);
+
+        let drawn_version = match self.buffers[surface.index()] {
+            Some(state) if self.screen.scrolled - state.scrolled < rows as u64 => {
+                let lines = (self.screen.scrolled - state.scrolled) as usize;
+
+                if lines > 0 {
+                    surface.copy_lines(lines * font_height, 0, (rows - lines) * font_height);
+                }
+
+                Some(state.version)
+            }
+            _ => {
+                surface.clear(PALETTE[DEFAULT_BG as usize]);
+                None
+            }
+        };
+
+        for y in 0..rows {
+            if drawn_version.is_some_and(|version| self.screen.row_versions[y] <= version) {
+                continue;
+            }
+
+            for x in 0..columns {
+                self.draw_cell(surface, x, y);
+            }
+        }
+
+        self.buffers[surface.index()] = Some(BufferState {
+            version: self.screen.version,
+            scrolled: self.screen.scrolled,
+        });
+    }
+
+    /// draw the copy of the screen and show it, there is nowhere to report a failure to
+    fn present(&mut self) {
+        let framebuffer: &'static (dyn Framebuffer + Sync) = self.framebuffer;
+
+        framebuffer.draw(&mut |surface| self.draw(surface));
+        let _ = framebuffer.flip();
+    }
+}
+
+impl fmt::Write for FramebufferConsoleInner {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+impl FramebufferConsole {
+    pub const fn new() -> Self {
+        Self {
+            inner: IRQSafeSpinLock::new(None),
+            display: SpinLock::new(None),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// start drawing on `framebuffer`, which is cleared with the first frame, anything written
+    /// before is dropped
+    pub fn init(&self, framebuffer: &'static (dyn Framebuffer + Sync)) -> Result<(), &'static str> {
+        let font = Font::parse(FONT)?;
+        let info = framebuffer.info();
+
+        let columns = info.width / font.width();
+        let rows = info.height / font.height();
+
+        if columns == 0 || rows == 0 {
+            return Err("framebuffer too small for the font");
+        }
+
+        self.display.lock(|display| {
+            *display = Some(Display {
+                framebuffer,
+                font,
+                screen: Screen::new(columns, rows),
+                buffers: vec![None; info.num_buffers],
+                suspended: false,
+            })
+        });
+        self.inner.lock(|inner| *inner = Some(FramebufferConsoleInner::new(columns, rows)));
+        self.dirty.store(true, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// start the thread that shows what was written, once per frame at most
+    ///
+    /// does nothing if the console was never set up
+    pub fn spawn_presenter(&'static self) {
+        if self.display.lock(|display| display.is_none()) {
+            return;
+        }
+
+        sched::spawn("framebuffer console", move || loop {
+            sched::sleep(FRAME_PERIOD);
+            self.present();
+        });
+    }
+
+    /// stop drawing until `resume()`, so that something else can use the framebuffer. waits for a
+    /// frame that is being drawn
+    pub fn suspend(&self) {
+        self.display.lock(|display| {
+            if let Some(display) = display {
+                display.suspended = true;
+            }
+        });
+    }
+
+    /// continue drawing, the buffers are drawn from scratch with the next frame since something
+    /// else drew on them
+    pub fn resume(&self) {
+        self.display.lock(|display| {
+            if let Some(display) = display {
+                display.suspended = false;
+                display.buffers.fill(None);
+            }
+        });
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// show what changed since the last frame, the console's lock is only held while copying
+    /// the changed rows
+    fn present(&self) {
+        if !self.dirty.swap(false, Ordering::Acquire) {
+            return;
+        }
+
+        self.display.lock(|display| self.update_and_present(display))
+    }
+
+    fn update_and_present(&self, display: &mut Option<Display>) {
+        if let Some(display) = display.as_mut().filter(|display| !display.suspended) {
+            self.inner.lock(|inner| {
+                if let Some(inner) = inner {
+                    display.screen.update_from(&inner.screen);
+                }
+            });
+
+            display.present();
+        }
+    }
+}
+
+impl interface::Write for FramebufferConsole {
+    fn write_bytes(&self, bytes: &[u8]) {
+        self.inner.lock(|inner| {
+            if let Some(inner) = inner {
+                inner.write_bytes(bytes);
+            }
+        });
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// takes the lock once for the whole text instead of once per piece
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        let result = self.inner.lock(|inner| match inner {
+            None => Ok(()),
+            Some(inner) => fmt::Write::write_fmt(inner, args),
+        });
+        self.dirty.store(true, Ordering::Release);
+
+        result
+    }
+
+    /// show what was written right away, for when the presenting thread won't get to run anymore,
+    /// like while panicking
+    ///
+    /// does nothing if a frame is being drawn already, which may be where the panic came from
+    fn flush(&self) {
+        self.display.try_lock(|display| {
+            self.dirty.store(false, Ordering::Relaxed);
+            self.update_and_present(display)
+        });
+    }
+}
+
+impl interface::Read for FramebufferConsole {
+    fn clear_rx(&self) {}
+}
+
+impl interface::Statistics for FramebufferConsole {
+    fn bytes_written(&self) -> usize {
+        self.inner.lock(|inner| inner.as_ref().map_or(0, |inner| inner.bytes_written))
+    }
+}
+
+impl interface::All for FramebufferConsole {}
@@ -0,0 +1,24 @@
+use core::fmt;
+
+use super::interface;
+
+/// the input until a real console is registered, anything logged meanwhile is kept in the kernel
+/// log and replayed to every console as it is registered
+pub struct NullConsole;
+
+impl interface::Write for NullConsole {
+    fn write_bytes(&self, _bytes: &[u8]) {}
+
+    fn write_fmt(&self, _args: fmt::Arguments) -> fmt::Result {
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+impl interface::Read for NullConsole {
+    fn clear_rx(&self) {}
+}
+
+impl interface::Statistics for NullConsole {}
+impl interface::All for NullConsole {}
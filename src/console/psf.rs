@@ -0,0 +1,105 @@
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_LEN: usize = 4;
+
+/// the font has 512 glyphs instead of 256
+const PSF1_MODE_512: u8 = 0x01;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HEADER_LEN: usize = 32;
+
+/// a bitmap font in the PC Screen Font format, version 1 or 2
+///
+/// glyphs are looked up by code point, a unicode table the font may have is ignored
+pub struct Font<'a> {
+    glyphs: &'a [u8],
+    num_glyphs: usize,
+    bytes_per_glyph: usize,
+    width: usize,
+    height: usize,
+}
+
+/// the bitmap of a single character, rows are padded to whole bytes with the leftmost pixel in
+/// the top bit
+#[derive(Copy, Clone)]
+pub struct Glyph<'a> {
+    bitmap: &'a [u8],
+    bytes_per_row: usize,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+impl<'a> Font<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, &'static str> {
+        let (header_len, num_glyphs, bytes_per_glyph, width, height) = if data.starts_with(&PSF2_MAGIC) {
+            if data.len() < PSF2_HEADER_LEN {
+                return Err("PSF2 header truncated");
+            }
+
+            let header_len = read_u32(data, 8) as usize;
+            let num_glyphs = read_u32(data, 16) as usize;
+            let bytes_per_glyph = read_u32(data, 20) as usize;
+            let height = read_u32(data, 24) as usize;
+            let width = read_u32(data, 28) as usize;
+
+            (header_len, num_glyphs, bytes_per_glyph, width, height)
+        } else if data.starts_with(&PSF1_MAGIC) {
+            if data.len() < PSF1_HEADER_LEN {
+                return Err("PSF1 header truncated");
+            }
+
+            let num_glyphs = if data[2] & PSF1_MODE_512 != 0 { 512 } else { 256 };
+            let height = data[3] as usize;
+
+            (PSF1_HEADER_LEN, num_glyphs, height, 8, height)
+        } else {
+            return Err("not a PSF font");
+        };
+
+        if width == 0 || height == 0 || bytes_per_glyph < width.div_ceil(8) * height {
+            return Err("PSF glyph size invalid");
+        }
+
+        let glyphs = header_len
+            .checked_add(num_glyphs * bytes_per_glyph)
+            .and_then(|end| data.get(header_len..end))
+            .ok_or("PSF glyphs truncated")?;
+
+        Ok(Self {
+            glyphs,
+            num_glyphs,
+            bytes_per_glyph,
+            width,
+            height,
+        })
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn glyph(&self, c: char) -> Option<Glyph<'a>> {
+        let index = c as usize;
+
+        if index >= self.num_glyphs {
+            return None;
+        }
+
+        Some(Glyph {
+            bitmap: &self.glyphs[index * self.bytes_per_glyph..(index + 1) * self.bytes_per_glyph],
+            bytes_per_row: self.width.div_ceil(8),
+        })
+    }
+}
+
+impl Glyph<'_> {
+    /// whether the pixel at `x`, `y` is part of the character
+    pub fn is_set(&self, x: usize, y: usize) -> bool {
+        self.bitmap[y * self.bytes_per_row + x / 8] & (0x80 >> (x % 8)) != 0
+    }
+}
@@ -2,5 +2,51 @@
 #[path = "../arch/aarch64/cpu/smp.rs"]
 mod arch_smp;
 
+use core::{sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+
+use crate::{bsp, cpu, memory, time, warn};
+
 #[allow(unused)]
 pub use arch_smp::*;
+
+/// the boot core is online from the start
+static NUM_CORES_ONLINE: AtomicUsize = AtomicUsize::new(1);
+
+/// release all secondary cores and wait for them to come online
+///
+/// # safety
+/// - must only be called once, by the boot core, after the kernel's memory and drivers have been
+///   initialized
+pub unsafe fn start_secondary_cores() {
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    let phys_entry_addr = match memory::mmu::try_kernel_virt_addr_to_phys_addr(virt_secondary_core_entry_addr()) {
+        Err(x) => {
+            warn!("cannot start secondary cores: {}", x);
+            return;
+        }
+        Ok(addr) => addr,
+    };
+
+    for core_id in (0..bsp::cpu::NUM_CORES).filter(|&id| id as u64 != bsp::cpu::BOOT_CORE_ID) {
+        bsp::cpu::release_secondary_core(core_id, phys_entry_addr);
+    }
+
+    let deadline = time::time_manager().uptime() + TIMEOUT;
+    while num_cores_online() < bsp::cpu::NUM_CORES && time::time_manager().uptime() < deadline {
+        cpu::nop();
+    }
+
+    if num_cores_online() < bsp::cpu::NUM_CORES {
+        warn!("only {} of {} cores came online", num_cores_online(), bsp::cpu::NUM_CORES);
+    }
+}
+
+/// called by each secondary core once it finished its early init
+pub fn mark_core_online() {
+    NUM_CORES_ONLINE.fetch_add(1, Ordering::Release);
+}
+
+pub fn num_cores_online() -> usize {
+    NUM_CORES_ONLINE.load(Ordering::Acquire)
+}
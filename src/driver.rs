@@ -1,7 +1,7 @@
 use alloc::vec::Vec;
 
 use crate::{
-    bsp::exception, info, synchronization::{InitStateLock, interface::ReadWriteEx}
+    bsp::exception, exception::asynchronous::irq_manager, info, synchronization::{InitStateLock, interface::ReadWriteEx}
 };
 use core::fmt;
 
@@ -15,6 +15,11 @@ pub mod interface {
             Ok(())
         }
 
+        /// per-core setup, run on every secondary core as it comes online
+        unsafe fn init_secondary_core(&self) -> Result<(), &'static str> {
+            Ok(())
+        }
+
         fn register_and_enable_irq_handler(&'static self, irq_number: &Self::IRQNumberType) -> Result<(), &'static str> {
             panic!("attempt to enable IRQ {} for device {}, but driver does not support this", irq_number, self.compatible())
         }
@@ -92,3 +97,23 @@ impl<T> DriverManager<T> where T: fmt::Display {
         })
     }
 }
+
+impl DriverManager<exception::asynchronous::IRQNumber> {
+    /// run the per-core driver setup on the calling secondary core and enable the registered IRQs
+    /// for it, IRQs that are shared between cores are simply enabled again
+    pub unsafe fn init_secondary_core(&self) -> Result<(), &'static str> {
+        self.descriptors.read(|descriptors| {
+            for descriptor in descriptors {
+                descriptor.device_driver.init_secondary_core()?;
+            }
+
+            for descriptor in descriptors {
+                if let Some(irq_number) = &descriptor.irq_number {
+                    irq_manager().enable(irq_number);
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
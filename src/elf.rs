@@ -0,0 +1,263 @@
+use alloc::sync::Arc;
+
+use crate::{bsp::memory::mmu::{KernelGranule, UserVirtAddrSpace}, memory::{mmu::{AccessPermissions, MemoryRegion, PageAddress, UserAddressSpace}, Address, Virtual}, sched};
+
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EV_CURRENT: u8 = 1;
+
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_AARCH64: u16 = 183;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+const PF_X: u32 = 1 << 0;
+const PF_W: u32 = 1 << 1;
+
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+const SHF_ALLOC: u64 = 1 << 1;
+
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+const SECTION_HEADER_SIZE: usize = 64;
+
+/// size of the stack every loaded image starts with, placed at the top of its address space
+const USER_STACK_SIZE: usize = 256 * 1024;
+
+struct ElfHeader {
+    e_type: u16,
+    e_machine: u16,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_phnum: u16,
+    e_shnum: u16,
+}
+
+struct ProgramHeader {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+}
+
+struct SectionHeader {
+    sh_type: u32,
+    sh_flags: u64,
+    sh_size: u64,
+}
+
+/// an image mapped into its own address space, ready to be started
+pub struct LoadedImage {
+    pub address_space: UserAddressSpace,
+    pub entry: Address<Virtual>,
+    pub stack_end_exclusive: Address<Virtual>,
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], &'static str> {
+    offset.checked_add(N)
+        .and_then(|end| data.get(offset..end))
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or("ELF image truncated")
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, &'static str> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, &'static str> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, &'static str> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+impl ElfHeader {
+    fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        if data.len() < ELF_HEADER_SIZE {
+            return Err("ELF image truncated");
+        }
+
+        let ident: [u8; 16] = read_bytes(data, 0)?;
+
+        if ident[0..4] != ELF_MAGIC {
+            return Err("not an ELF image");
+        }
+
+        if ident[4] != ELFCLASS64 || ident[5] != ELFDATA2LSB || ident[6] != EV_CURRENT {
+            return Err("not a little-endian ELF64 image");
+        }
+
+        let header = Self {
+            e_type: read_u16(data, 16)?,
+            e_machine: read_u16(data, 18)?,
+            e_entry: read_u64(data, 24)?,
+            e_phoff: read_u64(data, 32)?,
+            e_shoff: read_u64(data, 40)?,
+            e_phnum: read_u16(data, 56)?,
+            e_shnum: read_u16(data, 60)?,
+        };
+
+        if header.e_machine != EM_AARCH64 {
+            return Err("not an AArch64 image");
+        }
+
+        match header.e_type {
+            ET_EXEC => Ok(header),
+            ET_DYN => Err("position independent images need relocations, which are not supported"),
+            _ => Err("not an executable image"),
+        }
+    }
+
+    fn program_headers<'a>(&self, data: &'a [u8]) -> impl Iterator<Item = Result<ProgramHeader, &'static str>> + 'a {
+        let phoff = self.e_phoff as usize;
+
+        (0..self.e_phnum as usize).map(move |i| ProgramHeader::parse(data, phoff.saturating_add(i * PROGRAM_HEADER_SIZE)))
+    }
+
+    fn section_headers<'a>(&self, data: &'a [u8]) -> impl Iterator<Item = Result<SectionHeader, &'static str>> + 'a {
+        let shoff = self.e_shoff as usize;
+
+        (0..self.e_shnum as usize).map(move |i| SectionHeader::parse(data, shoff.saturating_add(i * SECTION_HEADER_SIZE)))
+    }
+}
+
+impl ProgramHeader {
+    fn parse(data: &[u8], offset: usize) -> Result<Self, &'static str> {
+        read_bytes::<PROGRAM_HEADER_SIZE>(data, offset)?;
+
+        Ok(Self {
+            p_type: read_u32(data, offset)?,
+            p_flags: read_u32(data, offset + 4)?,
+            p_offset: read_u64(data, offset + 8)?,
+            p_vaddr: read_u64(data, offset + 16)?,
+            p_filesz: read_u64(data, offset + 32)?,
+            p_memsz: read_u64(data, offset + 40)?,
+        })
+    }
+
+    /// the pages covered by the segment, which must lie in the user half of the address space
+    fn virt_region(&self) -> Result<MemoryRegion<Virtual>, &'static str> {
+        let start = self.p_vaddr as usize;
+        let end_exclusive = start.checked_add(self.p_memsz as usize).ok_or("segment wraps around")?;
+
+        // the first page stays unmapped to catch null pointers
+        if start < KernelGranule::SIZE || end_exclusive > UserVirtAddrSpace::SIZE {
+            return Err("segment overlaps kernel or reserved address space");
+        }
+
+        Ok(MemoryRegion::new(
+            PageAddress::from(Address::new(start).align_down_page()),
+            PageAddress::from(Address::new(end_exclusive).align_up_page()),
+        ))
+    }
+
+    fn file_data<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], &'static str> {
+        if self.p_filesz > self.p_memsz {
+            return Err("segment file size exceeds its memory size");
+        }
+
+        let start = self.p_offset as usize;
+
+        start.checked_add(self.p_filesz as usize)
+            .and_then(|end| data.get(start..end))
+            .ok_or("segment data truncated")
+    }
+}
+
+impl SectionHeader {
+    fn parse(data: &[u8], offset: usize) -> Result<Self, &'static str> {
+        read_bytes::<SECTION_HEADER_SIZE>(data, offset)?;
+
+        Ok(Self {
+            sh_type: read_u32(data, offset + 4)?,
+            sh_flags: read_u64(data, offset + 8)?,
+            sh_size: read_u64(data, offset + 32)?,
+        })
+    }
+
+    /// relocations that would have to be applied when loading the image
+    fn is_runtime_relocation(&self) -> bool {
+        matches!(self.sh_type, SHT_RELA | SHT_REL) && self.sh_flags & SHF_ALLOC != 0 && self.sh_size != 0
+    }
+}
+
+/// validate a statically linked AArch64 executable and map it into a fresh address space,
+/// together with a stack
+pub fn load(data: &[u8]) -> Result<LoadedImage, &'static str> {
+    let header = ElfHeader::parse(data)?;
+
+    for section_header in header.section_headers(data) {
+        if section_header?.is_runtime_relocation() {
+            return Err("image carries relocations, which are not supported");
+        }
+    }
+
+    let mut address_space = UserAddressSpace::new()?;
+    let mut entry_is_executable = false;
+
+    for program_header in header.program_headers(data) {
+        let program_header = program_header?;
+
+        match program_header.p_type {
+            PT_DYNAMIC | PT_INTERP => return Err("dynamically linked images are not supported"),
+            PT_LOAD if program_header.p_memsz != 0 => (),
+            _ => continue,
+        }
+
+        let virt_region = program_header.virt_region()?;
+        let file_data = program_header.file_data(data)?;
+
+        let executable = program_header.p_flags & PF_X != 0;
+        let writable = program_header.p_flags & PF_W != 0;
+
+        let access_permissions = match (writable, executable) {
+            (true, true) => return Err("segment is both writable and executable"),
+            (true, false) => AccessPermissions::ReadWrite,
+            (false, _) => AccessPermissions::ReadOnly,
+        };
+
+        // segments sharing a page would need conflicting attributes, so this is rejected as well
+        address_space.map_anonymous(&virt_region, access_permissions, !executable)?;
+
+        // everything behind the file data is left zeroed, which makes up the bss
+        address_space.write(Address::new(program_header.p_vaddr as usize), file_data)?;
+
+        if executable && virt_region.contains(Address::new(header.e_entry as usize)) {
+            entry_is_executable = true;
+        }
+    }
+
+    if !entry_is_executable {
+        return Err("entry point is not in an executable segment");
+    }
+
+    let stack_end_exclusive = Address::<Virtual>::new(UserVirtAddrSpace::SIZE);
+    let stack_region = MemoryRegion::new(
+        PageAddress::from(Address::new(UserVirtAddrSpace::SIZE - USER_STACK_SIZE)),
+        PageAddress::from(stack_end_exclusive),
+    );
+
+    address_space.map_anonymous(&stack_region, AccessPermissions::ReadWrite, true).map_err(|_| "image overlaps the user stack")?;
+
+    Ok(LoadedImage {
+        address_space,
+        entry: Address::new(header.e_entry as usize),
+        stack_end_exclusive,
+    })
+}
+
+impl LoadedImage {
+    /// start the image as a user thread called `name`
+    pub fn spawn(self, name: &'static str) -> sched::ThreadId {
+        sched::spawn_user(name, Arc::new(self.address_space), self.entry, self.stack_end_exclusive)
+    }
+}
@@ -3,6 +3,8 @@
 mod arch_exception;
 
 pub mod asynchronous;
+pub mod fixup;
+pub mod stack_guard;
 
 pub use arch_exception::*;
 
@@ -0,0 +1,25 @@
+use core::{mem::size_of, slice};
+
+use crate::{bsp, memory::{Address, Virtual}};
+
+/// an instruction that may fault on an untrusted address, and where execution resumes if it does
+#[repr(C)]
+struct ExceptionTableEntry {
+    fault_pc: usize,
+    fixup_pc: usize,
+}
+
+fn exception_table() -> &'static [ExceptionTableEntry] {
+    let raw = bsp::memory::exception_table();
+
+    // the linker script aligns the table to 8 bytes
+    unsafe { slice::from_raw_parts(raw.as_ptr() as *const ExceptionTableEntry, raw.len() / size_of::<ExceptionTableEntry>()) }
+}
+
+/// where to resume after a fault of the instruction at `fault_pc`, if it is allowed to fault
+pub fn search(fault_pc: Address<Virtual>) -> Option<Address<Virtual>> {
+    exception_table()
+        .iter()
+        .find(|entry| entry.fault_pc == fault_pc.as_usize())
+        .map(|entry| Address::new(entry.fixup_pc))
+}
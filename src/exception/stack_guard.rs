@@ -0,0 +1,45 @@
+use crate::{bsp, memory::{mmu::MemoryRegion, Address, Virtual}, synchronization::{interface::Mutex, IRQSafeSpinLock}};
+
+/// the most guard pages that can be registered at runtime
+const MAX_REGISTERED_GUARDS: usize = 64;
+
+#[derive(Copy, Clone)]
+struct StackGuard {
+    stack_name: &'static str,
+    virt_region: MemoryRegion<Virtual>,
+}
+
+/// guard pages of stacks created after boot, like the kernel stacks of threads. fixed in size so
+/// that looking up a fault never allocates
+static REGISTERED_GUARDS: IRQSafeSpinLock<[Option<StackGuard>; MAX_REGISTERED_GUARDS]> = IRQSafeSpinLock::new([None; MAX_REGISTERED_GUARDS]);
+
+/// make a fault within `virt_region`, which must be left unmapped below the stack, report an
+/// overflow of the stack called `stack_name`
+pub fn register(stack_name: &'static str, virt_region: MemoryRegion<Virtual>) -> Result<(), &'static str> {
+    REGISTERED_GUARDS.lock(|guards| {
+        let slot = guards.iter_mut().find(|slot| slot.is_none()).ok_or("no free stack guard slot")?;
+        *slot = Some(StackGuard { stack_name, virt_region });
+
+        Ok(())
+    })
+}
+
+/// forget the guard page at `virt_region`, before the stack above it is freed
+pub fn unregister(virt_region: &MemoryRegion<Virtual>) {
+    REGISTERED_GUARDS.lock(|guards| {
+        for slot in guards.iter_mut().filter(|slot| slot.is_some_and(|guard| guard.virt_region == *virt_region)) {
+            *slot = None;
+        }
+    });
+}
+
+/// the name of the stack whose guard page holds `addr`, if any
+pub fn search(addr: Address<Virtual>) -> Option<&'static str> {
+    let mut static_guards = bsp::memory::mmu::virt_stack_guard_regions();
+
+    if let Some((stack_name, _)) = static_guards.find(|(_, virt_region)| virt_region.contains(addr)) {
+        return Some(stack_name);
+    }
+
+    REGISTERED_GUARDS.lock(|guards| guards.iter().flatten().find(|guard| guard.virt_region.contains(addr)).map(|guard| guard.stack_name))
+}
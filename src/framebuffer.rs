@@ -0,0 +1,169 @@
+use crate::synchronization::{interface::ReadWriteEx, InitStateLock};
+
+pub mod interface {
+    use super::{Info, Surface};
+
+    pub trait Framebuffer {
+        fn info(&self) -> Info;
+
+        /// run `f` on the back buffer, which isn't shown before the next `flip()`
+        ///
+        /// neither this nor `flip()` may be called from IRQ context, both can take a while
+        fn draw(&self, f: &mut dyn FnMut(&mut Surface));
+
+        /// show the back buffer, the buffer shown so far becomes the new back buffer
+        fn flip(&self) -> Result<(), &'static str>;
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Info {
+    pub width: usize,
+    pub height: usize,
+
+    /// bytes per line, which may be more than `width` pixels
+    pub pitch: usize,
+
+    /// bits per pixel, either 16 (RGB565) or 32 (XRGB8888)
+    pub depth: usize,
+
+    pub num_buffers: usize,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// one buffer of a framebuffer
+pub struct Surface<'a> {
+    info: Info,
+
+    /// which of the `info.num_buffers` buffers this is
+    index: usize,
+    pixels: &'a mut [u8],
+}
+
+static CUR_FRAMEBUFFER: InitStateLock<Option<&'static (dyn interface::Framebuffer + Sync)>> = InitStateLock::new(None);
+
+impl Color {
+    pub const BLACK: Self = Self::new(0x00, 0x00, 0x00);
+    pub const RED: Self = Self::new(0xFF, 0x00, 0x00);
+    pub const GREEN: Self = Self::new(0x00, 0xFF, 0x00);
+    pub const BLUE: Self = Self::new(0x00, 0x00, 0xFF);
+    pub const WHITE: Self = Self::new(0xFF, 0xFF, 0xFF);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    const fn encode(self, depth: usize) -> u32 {
+        match depth {
+            16 => ((self.r as u32 >> 3) << 11) | ((self.g as u32 >> 2) << 5) | (self.b as u32 >> 3),
+            _ => ((self.r as u32) << 16) | ((self.g as u32) << 8) | self.b as u32,
+        }
+    }
+
+    /// the color as it reads back from a surface of `depth` bits per pixel
+    pub const fn quantized(self, depth: usize) -> Self {
+        Self::decode(self.encode(depth), depth)
+    }
+
+    const fn decode(pixel: u32, depth: usize) -> Self {
+        match depth {
+            16 => Self::new(((pixel >> 11) << 3) as u8, (((pixel >> 5) & 0x3F) << 2) as u8, ((pixel & 0x1F) << 3) as u8),
+            _ => Self::new((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8),
+        }
+    }
+}
+
+impl<'a> Surface<'a> {
+    /// `pixels` must hold `info.height` lines of `info.pitch` bytes
+    pub fn new(info: Info, index: usize, pixels: &'a mut [u8]) -> Self {
+        assert!(pixels.len() >= info.height * info.pitch);
+        assert!(info.depth == 16 || info.depth == 32);
+        assert!(index < info.num_buffers);
+
+        Self { info, index, pixels }
+    }
+
+    /// the same buffer has the same index every time it is drawn, so drawers can remember what
+    /// it shows
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    const fn bytes_per_pixel(&self) -> usize {
+        self.info.depth / 8
+    }
+
+    fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.info.pitch + x * self.bytes_per_pixel()
+    }
+
+    /// pixels outside of the surface are ignored
+    pub fn put_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.info.width && y < self.info.height {
+            let offset = self.offset(x, y);
+            let bytes_per_pixel = self.bytes_per_pixel();
+
+            self.pixels[offset..offset + bytes_per_pixel].copy_from_slice(&color.encode(self.info.depth).to_le_bytes()[..bytes_per_pixel]);
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.info.width || y >= self.info.height {
+            return None;
+        }
+
+        let offset = self.offset(x, y);
+        let mut bytes = [0; 4];
+        bytes[..self.bytes_per_pixel()].copy_from_slice(&self.pixels[offset..offset + self.bytes_per_pixel()]);
+
+        Some(Color::decode(u32::from_le_bytes(bytes), self.info.depth))
+    }
+
+    /// the part outside of the surface is ignored
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
+        let x_end = (x + width).min(self.info.width);
+        let y_end = (y + height).min(self.info.height);
+
+        if x >= x_end {
+            return;
+        }
+
+        let bytes_per_pixel = self.bytes_per_pixel();
+        let encoded = color.encode(self.info.depth).to_le_bytes();
+
+        for line in y..y_end {
+            let start = self.offset(x, line);
+            let end = self.offset(x_end, line);
+
+            for pixel in self.pixels[start..end].chunks_exact_mut(bytes_per_pixel) {
+                pixel.copy_from_slice(&encoded[..bytes_per_pixel]);
+            }
+        }
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        self.fill_rect(0, 0, self.info.width, self.info.height, color);
+    }
+
+    /// copy `count` lines starting at line `src` to line `dst`, the ranges may overlap
+    pub fn copy_lines(&mut self, src: usize, dst: usize, count: usize) {
+        let count = count.min(self.info.height.saturating_sub(src.max(dst)));
+
+        self.pixels.copy_within(src * self.info.pitch..(src + count) * self.info.pitch, dst * self.info.pitch);
+    }
+}
+
+pub fn register_framebuffer(new_framebuffer: &'static (dyn interface::Framebuffer + Sync)) {
+    CUR_FRAMEBUFFER.write(|framebuffer| *framebuffer = Some(new_framebuffer));
+}
+
+/// the display, if the board has one
+pub fn framebuffer() -> Option<&'static (dyn interface::Framebuffer + Sync)> {
+    CUR_FRAMEBUFFER.read(|framebuffer| *framebuffer)
+}
@@ -0,0 +1,305 @@
+pub mod dmesg;
+
+use core::{fmt, sync::atomic::{AtomicU8, Ordering}, time::Duration};
+
+use crate::{console, synchronization::{interface::Mutex, IRQSafeSpinLock}, time};
+
+pub mod interface {
+    pub trait Sink {
+        /// called for every record that passes the level filters
+        fn log(&self, record: &super::Record);
+    }
+}
+
+/// the most modules that can have a maximum level of their own
+const MAX_MODULE_FILTERS: usize = 16;
+
+/// module paths longer than this can't be filtered on
+const MAX_MODULE_PATH_LEN: usize = 64;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// the most verbose level that is let through
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u8)]
+pub enum LevelFilter {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+pub struct Record<'a> {
+    level: Level,
+    module_path: &'static str,
+    timestamp: Duration,
+    args: fmt::Arguments<'a>,
+}
+
+#[derive(Copy, Clone)]
+struct ModuleFilter {
+    path: [u8; MAX_MODULE_PATH_LEN],
+    path_len: usize,
+    max_level: LevelFilter,
+}
+
+/// prints records on every console, whatever their levels
+struct ConsoleSink;
+
+static CONSOLE_SINK: ConsoleSink = ConsoleSink;
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(if cfg!(feature = "debug_prints") { LevelFilter::Debug } else { LevelFilter::Info } as u8);
+
+/// the most verbose level of all module filters, so that most records are rejected without
+/// looking at them
+static MAX_MODULE_LEVEL: AtomicU8 = AtomicU8::new(LevelFilter::Off as u8);
+
+/// a fixed array, since the heap allocator logs as well and must not be entered while it is locked
+static MODULE_FILTERS: IRQSafeSpinLock<[Option<ModuleFilter>; MAX_MODULE_FILTERS]> = IRQSafeSpinLock::new([None; MAX_MODULE_FILTERS]);
+
+impl Level {
+    const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::Error),
+            2 => Some(Self::Warn),
+            3 => Some(Self::Info),
+            4 => Some(Self::Debug),
+            5 => Some(Self::Trace),
+            _ => None,
+        }
+    }
+
+    pub const fn as_char(&self) -> char {
+        match self {
+            Self::Error => 'E',
+            Self::Warn => 'W',
+            Self::Info => 'I',
+            Self::Debug => 'D',
+            Self::Trace => 'T',
+        }
+    }
+}
+
+impl LevelFilter {
+    const fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Error,
+            2 => Self::Warn,
+            3 => Self::Info,
+            4 => Self::Debug,
+            5 => Self::Trace,
+            _ => Self::Off,
+        }
+    }
+
+    pub const fn allows(&self, level: Level) -> bool {
+        level as u8 <= *self as u8
+    }
+}
+
+impl core::str::FromStr for LevelFilter {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err("unknown level"),
+        }
+    }
+}
+
+impl fmt::Display for LevelFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl<'a> Record<'a> {
+    pub const fn level(&self) -> Level {
+        self.level
+    }
+
+    /// the module the record was logged from, like `kernel::memory::mmu`
+    pub const fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+
+    /// the uptime the record was logged at
+    pub const fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+
+    pub const fn args(&self) -> &fmt::Arguments<'a> {
+        &self.args
+    }
+}
+
+impl ModuleFilter {
+    fn path(&self) -> &str {
+        // only ever copied from a `&str` as a whole
+        unsafe { core::str::from_utf8_unchecked(&self.path[..self.path_len]) }
+    }
+
+    /// a filter for `kernel::memory` applies to `kernel::memory::mmu` as well, but not to
+    /// `kernel::memory_map`
+    fn matches(&self, module_path: &str) -> bool {
+        match module_path.strip_prefix(self.path()) {
+            None => false,
+            Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        }
+    }
+}
+
+/// print `record` as a line of text, prefixed with its level and timestamp
+pub fn print_record(console: &(impl console::interface::Write + ?Sized), record: &Record) {
+    let timestamp = record.timestamp();
+
+    // module paths only help when tracking something down
+    let _ = match record.level() {
+        Level::Debug | Level::Trace => console.write_fmt(format_args!(
+            "[{} {:>3}.{:06}] {}: {}\n",
+            record.level().as_char(),
+            timestamp.as_secs(),
+            timestamp.subsec_micros(),
+            record.module_path(),
+            record.args()
+        )),
+        _ => console.write_fmt(format_args!(
+            "[{} {:>3}.{:06}] {}\n",
+            record.level().as_char(),
+            timestamp.as_secs(),
+            timestamp.subsec_micros(),
+            record.args()
+        )),
+    };
+}
+
+impl interface::Sink for ConsoleSink {
+    fn log(&self, record: &Record) {
+        print_record(console::console(), record);
+    }
+}
+
+/// the sink that prints to the registered consoles, for showing records again rather than logging
+/// them
+pub fn console_sink() -> &'static (dyn interface::Sink + Sync) {
+    &CONSOLE_SINK
+}
+
+pub fn max_level() -> LevelFilter {
+    LevelFilter::from_u8(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+/// the maximum level of all modules without a filter of their own
+pub fn set_max_level(max_level: LevelFilter) {
+    MAX_LEVEL.store(max_level as u8, Ordering::Relaxed);
+}
+
+fn update_max_module_level(filters: &[Option<ModuleFilter>]) {
+    let max = filters.iter().flatten().map(|filter| filter.max_level).max().unwrap_or(LevelFilter::Off);
+
+    MAX_MODULE_LEVEL.store(max as u8, Ordering::Relaxed);
+}
+
+/// override the maximum level for `module_path` and all modules below it, the longest matching
+/// path wins
+pub fn set_module_max_level(module_path: &str, max_level: LevelFilter) -> Result<(), &'static str> {
+    if module_path.len() > MAX_MODULE_PATH_LEN {
+        return Err("module path too long");
+    }
+
+    MODULE_FILTERS.lock(|filters| {
+        let slot = match filters.iter().position(|slot| slot.is_some_and(|filter| filter.path() == module_path)) {
+            Some(index) => &mut filters[index],
+            None => filters.iter_mut().find(|slot| slot.is_none()).ok_or("no free module filter slot")?,
+        };
+
+        let mut path = [0; MAX_MODULE_PATH_LEN];
+        path[..module_path.len()].copy_from_slice(module_path.as_bytes());
+
+        *slot = Some(ModuleFilter { path, path_len: module_path.len(), max_level });
+
+        update_max_module_level(filters);
+        Ok(())
+    })
+}
+
+/// let `module_path` follow the global maximum level again
+pub fn clear_module_max_level(module_path: &str) {
+    MODULE_FILTERS.lock(|filters| {
+        for slot in filters.iter_mut().filter(|slot| slot.is_some_and(|filter| filter.path() == module_path)) {
+            *slot = None;
+        }
+
+        update_max_module_level(filters);
+    })
+}
+
+/// call `f` with the path and maximum level of every module filter
+pub fn for_each_module_filter(mut f: impl FnMut(&str, LevelFilter)) {
+    MODULE_FILTERS.lock(|filters| {
+        for filter in filters.iter().flatten() {
+            f(filter.path(), filter.max_level);
+        }
+    })
+}
+
+/// whether a record of `level` logged from `module_path` would reach the sinks
+pub fn enabled(level: Level, module_path: &str) -> bool {
+    let max_level = max_level();
+    let max_module_level = LevelFilter::from_u8(MAX_MODULE_LEVEL.load(Ordering::Relaxed));
+
+    if !max_level.allows(level) && !max_module_level.allows(level) {
+        return false;
+    }
+
+    let module_level = match max_module_level {
+        LevelFilter::Off => None,
+        _ => MODULE_FILTERS.lock(|filters| {
+            filters
+                .iter()
+                .flatten()
+                .filter(|filter| filter.matches(module_path))
+                .max_by_key(|filter| filter.path_len)
+                .map(|filter| filter.max_level)
+        }),
+    };
+
+    module_level.unwrap_or(max_level).allows(level)
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, module_path: &'static str, args: fmt::Arguments) {
+    let record = Record {
+        level,
+        module_path,
+        timestamp: time::time_manager().uptime(),
+        args,
+    };
+
+    console::console_manager().log(&record);
+}
@@ -0,0 +1,243 @@
+use core::{fmt, mem::size_of, time::Duration};
+
+use super::{interface::Sink, Level, Record};
+use crate::{common::TruncatingWriter, synchronization::{interface::Mutex, IRQSafeSpinLock}};
+
+/// bytes kept for records, once full the oldest records are dropped
+const DMESG_SIZE: usize = 256 * 1024;
+
+/// longer messages are truncated
+const MAX_TEXT_LEN: usize = 512;
+
+/// timestamp in nanoseconds, module path pointer and length, text length and level
+const HEADER_SIZE: usize = size_of::<u64>() + size_of::<usize>() * 2 + size_of::<u16>() + size_of::<u8>();
+
+/// records are stored back to back in a byte ring, each a header followed by the text
+struct Dmesg {
+    buf: [u8; DMESG_SIZE],
+
+    /// offset of the oldest record
+    head: usize,
+
+    /// bytes in use, starting at `head`
+    len: usize,
+
+    /// sequence number of the oldest record
+    first_sequence: u64,
+    next_sequence: u64,
+}
+
+struct Header {
+    timestamp_nanos: u64,
+    module_path: &'static str,
+    text_len: usize,
+    level: Level,
+}
+
+/// the kernel log, which records everything logged since boot as far as it fits
+pub struct DmesgSink;
+
+/// a copy of a single record
+pub struct Entry {
+    sequence: u64,
+    level: Level,
+    timestamp: Duration,
+    module_path: &'static str,
+    text: [u8; MAX_TEXT_LEN],
+    text_len: usize,
+}
+
+/// reads the log in order, starting at the oldest record
+pub struct Reader {
+    next_sequence: u64,
+
+    /// offset of the record with `next_sequence`, only valid while it is not dropped
+    next_offset: usize,
+}
+
+static DMESG: IRQSafeSpinLock<Dmesg> = IRQSafeSpinLock::new(Dmesg::new());
+
+impl Dmesg {
+    const fn new() -> Self {
+        Self {
+            buf: [0; DMESG_SIZE],
+            head: 0,
+            len: 0,
+            first_sequence: 0,
+            next_sequence: 0,
+        }
+    }
+
+    fn write_bytes(&mut self, offset: usize, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.buf[(offset + i) % DMESG_SIZE] = *byte;
+        }
+    }
+
+    fn read_bytes(&self, offset: usize, bytes: &mut [u8]) {
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.buf[(offset + i) % DMESG_SIZE];
+        }
+    }
+
+    fn read_header(&self, offset: usize) -> Header {
+        let mut raw = [0; HEADER_SIZE];
+        self.read_bytes(offset, &mut raw);
+
+        Header::from_bytes(&raw)
+    }
+
+    fn drop_oldest(&mut self) {
+        let record_size = HEADER_SIZE + self.read_header(self.head).text_len;
+
+        self.head = (self.head + record_size) % DMESG_SIZE;
+        self.len -= record_size;
+        self.first_sequence += 1;
+    }
+
+    fn push(&mut self, header: &Header, text: &[u8]) {
+        let record_size = HEADER_SIZE + text.len();
+
+        while DMESG_SIZE - self.len < record_size {
+            self.drop_oldest();
+        }
+
+        let offset = (self.head + self.len) % DMESG_SIZE;
+
+        self.write_bytes(offset, &header.to_bytes());
+        self.write_bytes(offset + HEADER_SIZE, text);
+
+        self.len += record_size;
+        self.next_sequence += 1;
+    }
+}
+
+impl Header {
+    fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let fields = [
+            &self.timestamp_nanos.to_ne_bytes()[..],
+            &(self.module_path.as_ptr() as usize).to_ne_bytes(),
+            &self.module_path.len().to_ne_bytes(),
+            &(self.text_len as u16).to_ne_bytes(),
+            &[self.level as u8],
+        ];
+
+        let mut raw = [0; HEADER_SIZE];
+        let mut offset = 0;
+
+        for field in fields {
+            raw[offset..offset + field.len()].copy_from_slice(field);
+            offset += field.len();
+        }
+
+        raw
+    }
+
+    fn from_bytes(raw: &[u8; HEADER_SIZE]) -> Self {
+        let (timestamp_nanos, rest) = raw.split_at(size_of::<u64>());
+        let (module_path_ptr, rest) = rest.split_at(size_of::<usize>());
+        let (module_path_len, rest) = rest.split_at(size_of::<usize>());
+        let (text_len, level) = rest.split_at(size_of::<u16>());
+
+        let module_path_ptr = usize::from_ne_bytes(module_path_ptr.try_into().unwrap()) as *const u8;
+        let module_path_len = usize::from_ne_bytes(module_path_len.try_into().unwrap());
+
+        Self {
+            timestamp_nanos: u64::from_ne_bytes(timestamp_nanos.try_into().unwrap()),
+            // taken from a `&'static str` in `to_bytes`
+            module_path: unsafe { core::str::from_utf8_unchecked(core::slice::from_raw_parts(module_path_ptr, module_path_len)) },
+            text_len: u16::from_ne_bytes(text_len.try_into().unwrap()) as usize,
+            level: Level::from_u8(level[0]).unwrap_or(Level::Error),
+        }
+    }
+}
+
+impl Sink for DmesgSink {
+    fn log(&self, record: &Record) {
+        let mut text = [0; MAX_TEXT_LEN];
+        let mut writer = TruncatingWriter::new(&mut text);
+
+        // a truncated text is kept as far as it got
+        let _ = fmt::write(&mut writer, *record.args());
+
+        let header = Header {
+            timestamp_nanos: record.timestamp().as_nanos() as u64,
+            module_path: record.module_path(),
+            text_len: writer.written().len(),
+            level: record.level(),
+        };
+
+        DMESG.lock(|dmesg| dmesg.push(&header, writer.written()));
+    }
+}
+
+impl Entry {
+    #[allow(unused)]
+    pub const fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn text(&self) -> &str {
+        // only whole characters are copied in
+        unsafe { core::str::from_utf8_unchecked(&self.text[..self.text_len]) }
+    }
+
+    /// hand the entry to `sink` as if it was just logged
+    pub fn replay(&self, sink: &dyn Sink) {
+        sink.log(&Record {
+            level: self.level,
+            module_path: self.module_path,
+            timestamp: self.timestamp,
+            args: format_args!("{}", self.text()),
+        });
+    }
+}
+
+impl Reader {
+    pub const fn new() -> Self {
+        Self {
+            next_sequence: 0,
+            next_offset: 0,
+        }
+    }
+
+    /// the next record, skipping over any that were dropped since the last call
+    pub fn read_next(&mut self) -> Option<Entry> {
+        DMESG.lock(|dmesg| {
+            if self.next_sequence < dmesg.first_sequence {
+                self.next_sequence = dmesg.first_sequence;
+                self.next_offset = dmesg.head;
+            }
+
+            if self.next_sequence == dmesg.next_sequence {
+                return None;
+            }
+
+            let header = dmesg.read_header(self.next_offset);
+
+            let mut entry = Entry {
+                sequence: self.next_sequence,
+                level: header.level,
+                timestamp: Duration::from_nanos(header.timestamp_nanos),
+                module_path: header.module_path,
+                text: [0; MAX_TEXT_LEN],
+                text_len: header.text_len,
+            };
+            dmesg.read_bytes(self.next_offset + HEADER_SIZE, &mut entry.text[..header.text_len]);
+
+            self.next_sequence += 1;
+            self.next_offset = (self.next_offset + HEADER_SIZE + header.text_len) % DMESG_SIZE;
+
+            Some(entry)
+        })
+    }
+}
+
+/// hand every record still in the log to `sink`, like a console that was just attached
+pub fn replay(sink: &dyn Sink) {
+    let mut reader = Reader::new();
+
+    while let Some(entry) = reader.read_next() {
+        entry.replay(sink);
+    }
+}
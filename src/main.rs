@@ -22,18 +22,27 @@
 
 extern crate alloc;
 
+mod backtrace;
 mod bsp;
+mod chainload;
 mod comet;
 mod common;
 mod console;
 mod cpu;
 mod driver;
+mod elf;
 mod exception;
+mod framebuffer;
+mod log;
 mod memory;
 mod panic_wait;
 mod print;
+mod sched;
+mod shell;
 mod state;
+mod symbols;
 mod synchronization;
+mod syscall;
 mod time;
 
 #[no_mangle]
@@ -48,19 +57,46 @@ unsafe fn kernel_init() -> ! {
 
     driver::driver_manager().init_drivers_and_irqs();
 
+    shell::init();
+
     bsp::memory::mmu::kernel_add_mapping_records_for_precomputed();
 
+    sched::init_core("kernel_main");
+
     exception::asynchronous::local_irq_unmask();
     
     state::state_manager().transition_to_single_core_main();
 
+    cpu::smp::start_secondary_cores();
+
+    state::state_manager().transition_to_multi_core_main();
+
     // leave the unsafe world
     kernel_main();
 }
 
+#[no_mangle]
+unsafe fn kernel_init_secondary() -> ! {
+    exception::handling_init();
+
+    if let Err(x) = driver::driver_manager().init_secondary_core() {
+        panic!("error initializing drivers on core {}: {}", cpu::smp::core_id::<usize>(), x);
+    }
+
+    sched::init_core("idle");
+
+    exception::asynchronous::local_irq_unmask();
+
+    cpu::smp::mark_core_online();
+
+    // leave the unsafe world
+    kernel_main_secondary();
+}
+
 fn kernel_main() -> ! {
     info!("{} version {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
     info!("booting on: {}", bsp::board_name());
+    info!("cores online: {}", cpu::smp::num_cores_online());
 
     info!("enabled MMU, mappings:");
     memory::mmu::kernel_print_mappings();
@@ -82,5 +118,18 @@ fn kernel_main() -> ! {
     info!("kernel heap:");
     memory::heap_alloc::kernel_heap_allocator().print_usage();
 
+    info!("physical memory:");
+    memory::mmu::kernel_print_frame_usage();
+
+    info!("threads:");
+    sched::print_threads();
+
+    bsp::driver::framebuffer_console().spawn_presenter();
+    shell::spawn();
+
+    cpu::wait_forever();
+}
+
+fn kernel_main_secondary() -> ! {
     cpu::wait_forever();
 }
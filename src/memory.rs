@@ -4,6 +4,7 @@ use crate::{bsp, common};
 
 pub mod heap_alloc;
 pub mod mmu;
+pub mod user_access;
 
 pub trait AddressType: Copy + Clone + PartialOrd + PartialEq + Ord + Eq {}
 
@@ -112,4 +113,6 @@ impl fmt::Display for Address<Virtual> {
 pub fn init() {
     mmu::kernel_init_mmio_va_allocator();
     heap_alloc::kernel_init_heap_allocator();
+    mmu::kernel_init_frame_allocator();
+    mmu::kernel_init_dynamic_va_allocator();
 }
@@ -1,29 +1,28 @@
 use core::{alloc::{GlobalAlloc, Layout}, sync::atomic::{AtomicBool, Ordering}};
 
-use crate::{bsp, common, debug, info, memory::{Address, Virtual}, synchronization::{interface::Mutex, IRQSafeNullLock}, warn};
+use crate::{backtrace::Backtrace, bsp, common, info, memory::{Address, Virtual}, synchronization::{interface::Mutex, IRQSafeSpinLock}, trace, warn};
 
 use linked_list_allocator::Heap as LinkedListHeap;
 
 pub struct HeapAllocator {
-    inner: IRQSafeNullLock<LinkedListHeap>,
+    inner: IRQSafeSpinLock<LinkedListHeap>,
 }
 
 #[global_allocator]
 static KERNEL_HEAP_ALLOCATOR: HeapAllocator = HeapAllocator::new();
 
 #[inline(always)]
-fn debug_print_alloc_dealloc(operation: &'static str, ptr: *mut u8, layout: Layout) {
+fn trace_alloc_dealloc(operation: &'static str, ptr: *mut u8, layout: Layout) {
     let size = layout.size();
     let (size_h, size_unit) = common::size_human_readable_ceil(size);
     let addr = Address::<Virtual>::new(ptr as usize);
 
-    debug!("kernel heap: {}", operation);
-    debug!("    size:     {:#x} ({} {})", size, size_h, size_unit);
-    debug!("    start:    {}", addr);
-    debug!("    end excl: {}", addr + size);
-    debug!("");
-    // TODO: backtrace::Backtrace
-    debug!("    (backtrace functionality not yet implemented)");
+    trace!("kernel heap: {}", operation);
+    trace!("    size:     {:#x} ({} {})", size, size_h, size_unit);
+    trace!("    start:    {}", addr);
+    trace!("    end excl: {}", addr + size);
+    trace!("");
+    trace!("    backtrace:\n{}", Backtrace::current());
 }
 
 #[alloc_error_handler]
@@ -38,7 +37,7 @@ pub fn kernel_heap_allocator() -> &'static HeapAllocator {
 impl HeapAllocator {
     pub const fn new() -> Self {
         Self {
-            inner: IRQSafeNullLock::new(LinkedListHeap::empty()),
+            inner: IRQSafeSpinLock::new(LinkedListHeap::empty()),
         }
     }
 
@@ -69,7 +68,7 @@ unsafe impl GlobalAlloc for HeapAllocator {
             None => core::ptr::null_mut(),
             Some(allocation) => {
                 let ptr = allocation.as_ptr();
-                debug_print_alloc_dealloc("allocation", ptr, layout);
+                trace_alloc_dealloc("allocation", ptr, layout);
                 ptr
             }
         }
@@ -78,7 +77,7 @@ unsafe impl GlobalAlloc for HeapAllocator {
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         KERNEL_HEAP_ALLOCATOR.inner.lock(|inner| inner.deallocate(core::ptr::NonNull::new_unchecked(ptr), layout));
 
-        debug_print_alloc_dealloc("free", ptr, layout);
+        trace_alloc_dealloc("free", ptr, layout);
     }
 }
 
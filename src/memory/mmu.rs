@@ -2,14 +2,17 @@
 #[path = "../arch/aarch64/memory/mmu.rs"]
 mod arch_mmu;
 
+mod bitmap_page_alloc;
 mod mapping_record;
 mod page_alloc;
 mod translation_table;
 mod types;
+mod user_address_space;
 
 use interface::MMU;
 use translation_table::interface::TranslationTable;
 pub use types::*;
+pub use user_address_space::*;
 
 use core::{fmt, num::NonZeroUsize};
 
@@ -31,6 +34,13 @@ pub mod interface {
         /// - changes the HW's global state
         unsafe fn enable_mmu_and_caching(&self, phys_tables_base_addr: Address<Physical>) -> Result<(), MMUEnableError>;
 
+        /// switch the lower half of the address space of the current core to the given tables,
+        /// or unmap it completely for `None`
+        ///
+        /// # safety
+        /// - the tables must stay alive and unchanged, apart from adding mappings, while active
+        unsafe fn set_user_translation_tables(&self, phys_tables_base_addr: Option<Address<Physical>>);
+
         fn is_enabled(&self) -> bool;
     }
 }
@@ -55,7 +65,7 @@ unsafe fn kernel_map_at_unchecked(name: &'static str, virt_region: &MemoryRegion
     Ok(())
 }
 
-fn try_kernel_virt_addr_to_phys_addr(virt_addr: Address<Virtual>) -> Result<Address<Physical>, &'static str> {
+pub fn try_kernel_virt_addr_to_phys_addr(virt_addr: Address<Virtual>) -> Result<Address<Physical>, &'static str> {
     bsp::memory::mmu::kernel_translation_tables()
         .read(|tables| tables.try_virt_addr_to_phys_addr(virt_addr))
 }
@@ -100,6 +110,35 @@ pub fn kernel_init_mmio_va_allocator() {
     page_alloc::kernel_mmio_va_allocator().lock(|allocator| allocator.init(region));
 }
 
+pub fn kernel_init_frame_allocator() {
+    bitmap_page_alloc::kernel_frame_allocator().lock(|allocator| {
+        for (name, region) in bsp::memory::mmu::phys_free_dram_zones() {
+            allocator.add_zone(name, region);
+        }
+    });
+}
+
+/// hand DRAM that was found after init to the frame allocator
+pub fn kernel_add_frame_zone(name: &'static str, phys_region: MemoryRegion<Physical>) {
+    bitmap_page_alloc::kernel_frame_allocator().lock(|allocator| allocator.add_zone(name, phys_region));
+}
+
+pub fn kernel_init_dynamic_va_allocator() {
+    let region = bsp::memory::mmu::virt_dynamic_map_region();
+
+    bitmap_page_alloc::kernel_dynamic_va_allocator().lock(|allocator| allocator.add_zone("Dynamic map", region));
+}
+
+/// allocate `num_pages` physically contiguous pages of DRAM
+pub fn kernel_alloc_frames(num_pages: NonZeroUsize) -> Result<MemoryRegion<Physical>, &'static str> {
+    bitmap_page_alloc::kernel_frame_allocator().lock(|allocator| allocator.alloc(num_pages))
+}
+
+/// return pages previously handed out by `kernel_alloc_frames()`
+pub fn kernel_free_frames(phys_region: &MemoryRegion<Physical>) -> Result<(), &'static str> {
+    bitmap_page_alloc::kernel_frame_allocator().lock(|allocator| allocator.free(phys_region))
+}
+
 pub fn kernel_add_mapping_record(name: &'static str, virt_region: &MemoryRegion<Virtual>, phys_region: &MemoryRegion<Physical>, attr: &AttributeFields) {
     mapping_record::kernel_add(name, virt_region, phys_region, attr);
 }
@@ -124,6 +163,7 @@ pub unsafe fn kernel_map_mmio(name: &'static str, mmio_descriptor: &MMIODescript
             mem_attributes: MemAttributes::Device,
             access_permissions: AccessPermissions::ReadWrite,
             execute_never: true,
+            user_accessible: false,
         })?;
 
         virt_region.start_addr()
@@ -132,6 +172,89 @@ pub unsafe fn kernel_map_mmio(name: &'static str, mmio_descriptor: &MMIODescript
     Ok(virt_addr + offset_into_start_page)
 }
 
+/// map `phys_region` at free kernel virtual addresses, which also works after init
+///
+/// # safety
+/// - same as `kernel_map_at_unchecked()`
+pub unsafe fn kernel_map(name: &'static str, phys_region: &MemoryRegion<Physical>, attr: &AttributeFields) -> Result<MemoryRegion<Virtual>, &'static str> {
+    if attr.user_accessible {
+        return Err("kernel mappings must not be user accessible");
+    }
+
+    let num_pages = NonZeroUsize::new(phys_region.num_pages()).ok_or("requested 0 pages")?;
+    let virt_region = bitmap_page_alloc::kernel_dynamic_va_allocator().lock(|allocator| allocator.alloc(num_pages))?;
+
+    if let Err(x) = kernel_map_at_unchecked(name, &virt_region, phys_region, attr) {
+        bitmap_page_alloc::kernel_dynamic_va_allocator().lock(|allocator| allocator.free(&virt_region))?;
+        return Err(x);
+    }
+
+    Ok(virt_region)
+}
+
+/// map `phys_region` as a kernel stack at free kernel virtual addresses, with the page below it
+/// reserved but left unmapped, returns the stack and its guard page
+///
+/// # safety
+/// - same as `kernel_map_at_unchecked()`
+pub unsafe fn kernel_map_stack(name: &'static str, phys_region: &MemoryRegion<Physical>) -> Result<(MemoryRegion<Virtual>, MemoryRegion<Virtual>), &'static str> {
+    let num_pages = NonZeroUsize::new(phys_region.num_pages()).ok_or("requested 0 pages")?;
+    let mut virt_region = bitmap_page_alloc::kernel_dynamic_va_allocator().lock(|allocator| allocator.alloc(num_pages.saturating_add(1)))?;
+    let guard_region = virt_region.take_first_n_pages(NonZeroUsize::MIN)?;
+
+    let attr = AttributeFields {
+        mem_attributes: MemAttributes::CacheableDRAM,
+        access_permissions: AccessPermissions::ReadWrite,
+        execute_never: true,
+        user_accessible: false,
+    };
+
+    if let Err(x) = kernel_map_at_unchecked(name, &virt_region, phys_region, &attr) {
+        bitmap_page_alloc::kernel_dynamic_va_allocator().lock(|allocator| {
+            allocator.free(&guard_region)?;
+            allocator.free(&virt_region)
+        })?;
+
+        return Err(x);
+    }
+
+    Ok((virt_region, guard_region))
+}
+
+/// drop `name` from the users of the mapping at `virt_region`, which is unmapped from all cores
+/// once its last user is gone
+///
+/// # safety
+/// - `name` must not access the memory through `virt_region` anymore
+pub unsafe fn kernel_unmap(name: &'static str, virt_region: &MemoryRegion<Virtual>) -> Result<(), &'static str> {
+    // the record keeps `name` if unmapping fails, so the mapping stays accounted for
+    let unmapped = mapping_record::kernel_remove_user(name, virt_region, || {
+        bsp::memory::mmu::kernel_translation_tables()
+            .write(|tables| tables.unmap_at(virt_region))
+    })?;
+
+    if !unmapped {
+        return Ok(());
+    }
+
+    // the MMIO window is a bump allocator and never gets its addresses back
+    if bsp::memory::mmu::virt_dynamic_map_region().contains(virt_region.start_addr()) {
+        bitmap_page_alloc::kernel_dynamic_va_allocator().lock(|allocator| allocator.free(virt_region))?;
+    }
+
+    Ok(())
+}
+
+/// unmap a stack mapped by `kernel_map_stack()` and give back its guard page
+///
+/// # safety
+/// - same as `kernel_unmap()`
+pub unsafe fn kernel_unmap_stack(name: &'static str, virt_region: &MemoryRegion<Virtual>, guard_region: &MemoryRegion<Virtual>) -> Result<(), &'static str> {
+    kernel_unmap(name, virt_region)?;
+
+    bitmap_page_alloc::kernel_dynamic_va_allocator().lock(|allocator| allocator.free(guard_region))
+}
+
 pub fn try_kernel_virt_page_addr_to_phys_page_addr(virt_page_addr: PageAddress<Virtual>) -> Result<PageAddress<Physical>, &'static str> {
     bsp::memory::mmu::kernel_translation_tables()
         .read(|tables| tables.try_virt_page_addr_to_phys_page_addr(virt_page_addr))
@@ -146,6 +269,10 @@ pub fn kernel_print_mappings() {
     mapping_record::kernel_print()
 }
 
+pub fn kernel_print_frame_usage() {
+    bitmap_page_alloc::kernel_frame_allocator().lock(|allocator| allocator.print_usage())
+}
+
 /// # safety
 /// - crucial function during kernel init. changes the complete memory view of the processor.
 #[inline(always)]
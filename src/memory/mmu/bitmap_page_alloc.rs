@@ -0,0 +1,219 @@
+use alloc::{vec, vec::Vec};
+use core::num::NonZeroUsize;
+
+use crate::{bsp::memory::mmu::KernelGranule, common, info, memory::{AddressType, Physical, Virtual}, synchronization::IRQSafeSpinLock, warn};
+
+use super::{MemoryRegion, PageAddress};
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+
+/// a contiguous range of pages, tracked with one bit per page
+struct Zone<ATYPE: AddressType> {
+    name: &'static str,
+    region: MemoryRegion<ATYPE>,
+
+    /// a set bit marks an allocated page
+    bitmap: Vec<u64>,
+    num_free_pages: usize,
+
+    /// page index the next search starts at, so that fresh allocations don't rescan the
+    /// allocated front of the zone every time
+    next_fit: usize,
+}
+
+/// hands out contiguous runs of pages that can be freed again, first looking in the zone added
+/// first
+pub struct BitmapPageAllocator<ATYPE: AddressType> {
+    zones: Vec<Zone<ATYPE>>,
+}
+
+/// DRAM not occupied by the kernel image
+static KERNEL_FRAME_ALLOCATOR: IRQSafeSpinLock<BitmapPageAllocator<Physical>> = IRQSafeSpinLock::new(BitmapPageAllocator::new());
+
+/// the kernel virtual address window used by `kernel_map()`
+static KERNEL_DYNAMIC_VA_ALLOCATOR: IRQSafeSpinLock<BitmapPageAllocator<Virtual>> = IRQSafeSpinLock::new(BitmapPageAllocator::new());
+
+pub fn kernel_frame_allocator() -> &'static IRQSafeSpinLock<BitmapPageAllocator<Physical>> {
+    &KERNEL_FRAME_ALLOCATOR
+}
+
+pub fn kernel_dynamic_va_allocator() -> &'static IRQSafeSpinLock<BitmapPageAllocator<Virtual>> {
+    &KERNEL_DYNAMIC_VA_ALLOCATOR
+}
+
+impl<ATYPE: AddressType> Zone<ATYPE> {
+    fn new(name: &'static str, region: MemoryRegion<ATYPE>) -> Self {
+        let num_pages = region.num_pages();
+
+        Self {
+            name,
+            region,
+            bitmap: vec![0; num_pages.div_ceil(BITS_PER_WORD)],
+            num_free_pages: num_pages,
+            next_fit: 0,
+        }
+    }
+
+    fn num_pages(&self) -> usize {
+        self.region.num_pages()
+    }
+
+    fn is_allocated(&self, page: usize) -> bool {
+        self.bitmap[page / BITS_PER_WORD] & (1 << (page % BITS_PER_WORD)) != 0
+    }
+
+    fn set_allocated(&mut self, first_page: usize, num_pages: usize, allocated: bool) {
+        for page in first_page..(first_page + num_pages) {
+            let word = &mut self.bitmap[page / BITS_PER_WORD];
+            let mask = 1 << (page % BITS_PER_WORD);
+
+            if allocated {
+                *word |= mask;
+            } else {
+                *word &= !mask;
+            }
+        }
+    }
+
+    /// first run of `num_pages` free pages that starts in `from..to`
+    fn find_free_run_in(&self, from: usize, to: usize, num_pages: usize) -> Option<usize> {
+        let mut run_start = from;
+        let mut page = from;
+
+        while page < self.num_pages() && run_start < to {
+            // whole words of allocated pages are skipped at once
+            if page % BITS_PER_WORD == 0 && self.bitmap[page / BITS_PER_WORD] == u64::MAX {
+                page += BITS_PER_WORD;
+                run_start = page;
+            } else if self.is_allocated(page) {
+                page += 1;
+                run_start = page;
+            } else {
+                page += 1;
+
+                if page - run_start == num_pages {
+                    return Some(run_start);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn alloc(&mut self, num_pages: usize) -> Option<MemoryRegion<ATYPE>> {
+        if num_pages > self.num_free_pages {
+            return None;
+        }
+
+        let first_page = self.find_free_run_in(self.next_fit, self.num_pages(), num_pages)
+            .or_else(|| self.find_free_run_in(0, self.next_fit, num_pages))?;
+
+        self.set_allocated(first_page, num_pages, true);
+        self.num_free_pages -= num_pages;
+        self.next_fit = (first_page + num_pages) % self.num_pages();
+
+        Some(self.page_region(first_page, num_pages))
+    }
+
+    fn free(&mut self, region: &MemoryRegion<ATYPE>) -> Result<(), &'static str> {
+        let first_page = self.page_index(region.start_page_addr());
+        let num_pages = region.num_pages();
+
+        if (first_page..(first_page + num_pages)).any(|page| !self.is_allocated(page)) {
+            return Err("freeing pages that are not allocated");
+        }
+
+        self.set_allocated(first_page, num_pages, false);
+        self.num_free_pages += num_pages;
+
+        Ok(())
+    }
+
+    fn largest_free_run(&self) -> usize {
+        let mut largest = 0;
+        let mut run = 0;
+
+        for page in 0..self.num_pages() {
+            if self.is_allocated(page) {
+                run = 0;
+            } else {
+                run += 1;
+                largest = largest.max(run);
+            }
+        }
+
+        largest
+    }
+
+    fn page_index(&self, page_addr: PageAddress<ATYPE>) -> usize {
+        (page_addr.into_inner() - self.region.start_addr()).as_usize() >> KernelGranule::SHIFT
+    }
+
+    fn page_region(&self, first_page: usize, num_pages: usize) -> MemoryRegion<ATYPE> {
+        let start = self.region.start_page_addr().checked_offset(first_page as isize).unwrap();
+
+        MemoryRegion::new(start, start.checked_offset(num_pages as isize).unwrap())
+    }
+
+    fn contains(&self, region: &MemoryRegion<ATYPE>) -> bool {
+        self.region.start_page_addr() <= region.start_page_addr() && region.end_exclusive_page_addr() <= self.region.end_exclusive_page_addr()
+    }
+}
+
+impl<ATYPE: AddressType> BitmapPageAllocator<ATYPE> {
+    pub const fn new() -> Self {
+        Self {
+            zones: Vec::new(),
+        }
+    }
+
+    pub fn add_zone(&mut self, name: &'static str, region: MemoryRegion<ATYPE>) {
+        if region.num_pages() == 0 {
+            warn!("{}: empty zone", name);
+            return;
+        }
+
+        if self.zones.iter().any(|zone| zone.region.overlaps(&region)) {
+            warn!("{}: zone overlaps an existing one", name);
+            return;
+        }
+
+        self.zones.push(Zone::new(name, region));
+    }
+
+    /// allocate `num_pages` physically contiguous pages
+    pub fn alloc(&mut self, num_pages: NonZeroUsize) -> Result<MemoryRegion<ATYPE>, &'static str> {
+        if self.zones.is_empty() {
+            return Err("allocator not initialized");
+        }
+
+        self.zones.iter_mut()
+            .find_map(|zone| zone.alloc(num_pages.get()))
+            .ok_or("not enough contiguous free pages")
+    }
+
+    /// return pages previously handed out by `alloc`, a region may be returned in parts
+    pub fn free(&mut self, region: &MemoryRegion<ATYPE>) -> Result<(), &'static str> {
+        if region.num_pages() == 0 {
+            return Ok(());
+        }
+
+        self.zones.iter_mut()
+            .find(|zone| zone.contains(region))
+            .ok_or("freeing pages outside of any zone")?
+            .free(region)
+    }
+}
+
+impl BitmapPageAllocator<Physical> {
+    pub fn print_usage(&self) {
+        for zone in self.zones.iter() {
+            let (size, unit) = common::size_human_readable_ceil(zone.region.size());
+
+            info!("    {}: {}..{} ({} {})", zone.name, zone.region.start_addr(), zone.region.end_exclusive_page_addr().into_inner(), size, unit);
+            info!("        total: {} pages", zone.num_pages());
+            info!("        free: {} pages", zone.num_free_pages);
+            info!("        largest contiguous free: {} pages", zone.largest_free_run());
+        }
+    }
+}
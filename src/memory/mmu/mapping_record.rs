@@ -1,6 +1,6 @@
 use alloc::{vec, vec::Vec};
 
-use crate::{bsp, common, info, memory::{mmu::AccessPermissions, Address, Physical, Virtual}, synchronization::{interface::ReadWriteEx, InitStateLock}};
+use crate::{bsp, common, info, memory::{mmu::AccessPermissions, Address, Physical, Virtual}, synchronization::{interface::ReadWriteEx, RwLock}};
 
 use super::{AttributeFields, MMIODescriptor, MemAttributes, MemoryRegion};
 
@@ -16,7 +16,7 @@ struct MappingRecord {
     inner: Vec<MappingRecordEntry>,
 }
 
-static KERNEL_MAPPING_RECORD: InitStateLock<MappingRecord> = InitStateLock::new(MappingRecord::new());
+static KERNEL_MAPPING_RECORD: RwLock<MappingRecord> = RwLock::new(MappingRecord::new());
 
 impl MappingRecordEntry {
     pub fn new(name: &'static str, virt_region: &MemoryRegion<Virtual>, phys_region: &MemoryRegion<Physical>, attr: &AttributeFields) -> Self {
@@ -32,6 +32,10 @@ impl MappingRecordEntry {
     pub fn add_user(&mut self, user: &'static str) {
         self.users.push(user);
     }
+
+    fn matches(&self, virt_region: &MemoryRegion<Virtual>) -> bool {
+        self.virt_start_addr == virt_region.start_addr() && self.num_pages == virt_region.num_pages()
+    }
 }
 
 impl MappingRecord {
@@ -70,6 +74,25 @@ impl MappingRecord {
         self.sort();
     }
 
+    /// returns whether `user` was the last one, in which case `unmap` is run and the entry is
+    /// only removed once it succeeded
+    pub fn remove_user(&mut self, user: &'static str, virt_region: &MemoryRegion<Virtual>, unmap: impl FnOnce() -> Result<(), &'static str>) -> Result<bool, &'static str> {
+        let index = self.inner.iter().position(|x| x.matches(virt_region)).ok_or("no mapping recorded for region")?;
+        let entry = &mut self.inner[index];
+
+        let user_index = entry.users.iter().position(|x| *x == user).ok_or("mapping is not used by this user")?;
+
+        if entry.users.len() > 1 {
+            entry.users.remove(user_index);
+            return Ok(false);
+        }
+
+        unmap()?;
+        self.inner.remove(index);
+
+        Ok(true)
+    }
+
     pub fn print(&self) {
         info!("    -------------------------------------------------------------------------------------------------------------------------------------------");
         info!("    {:^44}     {:^30}   {:^7}   {:^9}   {:^35}", "Virtual", "Physical", "Size", "Attr", "Entity");
@@ -88,6 +111,7 @@ impl MappingRecord {
 
             let attr = match i.attribute_fields.mem_attributes {
                 MemAttributes::CacheableDRAM => "C",
+                MemAttributes::NonCacheableDRAM => "NC",
                 MemAttributes::Device => "Dev",
             };
 
@@ -117,6 +141,10 @@ pub fn kernel_add(name: &'static str, virt_region: &MemoryRegion<Virtual>, phys_
     KERNEL_MAPPING_RECORD.write(|mr| mr.add(name, virt_region, phys_region, attr))
 }
 
+pub fn kernel_remove_user(user: &'static str, virt_region: &MemoryRegion<Virtual>, unmap: impl FnOnce() -> Result<(), &'static str>) -> Result<bool, &'static str> {
+    KERNEL_MAPPING_RECORD.write(|mr| mr.remove_user(user, virt_region, unmap))
+}
+
 pub fn kernel_find_and_insert_mmio_duplicate(mmio_descriptor: &MMIODescriptor, new_user: &'static str) -> Option<Address<Virtual>> {
     let phys_region: MemoryRegion<Physical> = (*mmio_descriptor).into();
 
@@ -1,6 +1,6 @@
 use core::num::NonZeroUsize;
 
-use crate::{memory::{AddressType, Virtual}, synchronization::IRQSafeNullLock, warn};
+use crate::{memory::{AddressType, Virtual}, synchronization::IRQSafeSpinLock, warn};
 
 use super::MemoryRegion;
 
@@ -8,9 +8,9 @@ pub struct PageAllocator<ATYPE: AddressType> {
     pool: Option<MemoryRegion<ATYPE>>,
 }
 
-static KERNEL_MMIO_VA_ALLOCATOR: IRQSafeNullLock<PageAllocator<Virtual>> = IRQSafeNullLock::new(PageAllocator::new());
+static KERNEL_MMIO_VA_ALLOCATOR: IRQSafeSpinLock<PageAllocator<Virtual>> = IRQSafeSpinLock::new(PageAllocator::new());
 
-pub fn kernel_mmio_va_allocator() -> &'static IRQSafeNullLock<PageAllocator<Virtual>> {
+pub fn kernel_mmio_va_allocator() -> &'static IRQSafeSpinLock<PageAllocator<Virtual>> {
     &KERNEL_MMIO_VA_ALLOCATOR
 }
 
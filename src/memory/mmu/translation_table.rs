@@ -26,8 +26,19 @@ pub mod interface {
         ///   MMU code
         unsafe fn map_at(&mut self, virt_region: &MemoryRegion<Virtual>, phys_region: &MemoryRegion<Physical>, attr: &AttributeFields) -> Result<(), &'static str>;
 
+        /// invalidate the pages of `virt_region` and drop them from the TLBs of all cores, fails
+        /// without changing anything if any page is not mapped
+        ///
+        /// # safety
+        /// - the memory behind `virt_region` must not be accessed through it anymore
+        unsafe fn unmap_at(&mut self, virt_region: &MemoryRegion<Virtual>) -> Result<(), &'static str>;
+
         fn try_virt_page_addr_to_phys_page_addr(&self, virt_page_addr: PageAddress<Virtual>) -> Result<PageAddress<Physical>, &'static str>;
         fn try_page_attributes(&self, virt_page_addr: PageAddress<Virtual>) -> Result<AttributeFields, &'static str>;
         fn try_virt_addr_to_phys_addr(&self, virt_addr: Address<Virtual>) -> Result<Address<Physical>, &'static str>;
+
+        /// the physical address of the top-level table, as loaded into the translation table base
+        /// register
+        fn phys_base_address(&self) -> Result<Address<Physical>, &'static str>;
     }
 }
@@ -16,6 +16,10 @@ pub struct MemoryRegion<ATYPE: AddressType> {
 #[derive(Copy, Clone, Debug, Eq, PartialOrd, PartialEq)]
 pub enum MemAttributes {
     CacheableDRAM,
+
+    /// uncached, but without the ordering restrictions of `Device`, e.g. for framebuffers
+    NonCacheableDRAM,
+
     Device,
 }
 
@@ -30,6 +34,9 @@ pub struct AttributeFields {
     pub mem_attributes: MemAttributes,
     pub access_permissions: AccessPermissions,
     pub execute_never: bool,
+
+    /// accessible from EL0, `execute_never` then applies to EL0 while EL1 may never execute it
+    pub user_accessible: bool,
 }
 
 #[derive(Copy, Clone)]
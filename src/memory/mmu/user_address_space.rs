@@ -0,0 +1,165 @@
+use alloc::{alloc::alloc_zeroed, boxed::Box, vec::Vec};
+use core::{alloc::Layout, num::NonZeroUsize};
+
+use crate::{bsp::memory::mmu::UserTranslationTable, cpu, memory::{Address, Physical, Virtual}, warn};
+
+use super::{arch_mmu, interface::MMU, kernel_alloc_frames, kernel_free_frames, kernel_map, kernel_unmap, translation_table::interface::TranslationTable, AccessPermissions, AttributeFields, MemAttributes, MemoryRegion};
+
+/// the kernel's mappings of user regions, as listed in the mapping record
+const KERNEL_VIEW_NAME: &str = "User region";
+
+/// a region of a user address space, backed by zeroed frames which the kernel maps as well
+struct UserRegion {
+    virt_region: MemoryRegion<Virtual>,
+    phys_region: MemoryRegion<Physical>,
+
+    /// where the kernel reaches the frames, whichever address space is active
+    kernel_virt_region: MemoryRegion<Virtual>,
+    execute_never: bool,
+}
+
+/// the lower half of the virtual address space as seen by a user process, translated through
+/// TTBR0 while the kernel stays mapped through TTBR1
+pub struct UserAddressSpace {
+    tables: Box<UserTranslationTable>,
+    phys_tables_base_addr: Address<Physical>,
+    regions: Vec<UserRegion>,
+}
+
+impl UserRegion {
+    /// allocate and zero the frames for `virt_region` and map them for the kernel
+    fn new(virt_region: &MemoryRegion<Virtual>, execute_never: bool) -> Result<Self, &'static str> {
+        let num_pages = NonZeroUsize::new(virt_region.num_pages()).ok_or("empty user region")?;
+        let phys_region = kernel_alloc_frames(num_pages)?;
+
+        let attr = AttributeFields {
+            mem_attributes: MemAttributes::CacheableDRAM,
+            access_permissions: AccessPermissions::ReadWrite,
+            execute_never: true,
+            user_accessible: false,
+        };
+
+        let kernel_virt_region = match unsafe { kernel_map(KERNEL_VIEW_NAME, &phys_region, &attr) } {
+            Err(x) => {
+                let _ = kernel_free_frames(&phys_region);
+                return Err(x);
+            }
+            Ok(region) => region,
+        };
+
+        // frames still hold whatever their previous user left in them
+        unsafe { core::ptr::write_bytes(kernel_virt_region.start_addr().as_usize() as *mut u8, 0, kernel_virt_region.size()) };
+
+        Ok(Self {
+            virt_region: *virt_region,
+            phys_region,
+            kernel_virt_region,
+            execute_never,
+        })
+    }
+}
+
+impl Drop for UserRegion {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(x) = kernel_unmap(KERNEL_VIEW_NAME, &self.kernel_virt_region) {
+                warn!("cannot unmap user region: {}", x);
+                return;
+            }
+        }
+
+        if let Err(x) = kernel_free_frames(&self.phys_region) {
+            warn!("cannot free user region: {}", x);
+        }
+    }
+}
+
+impl UserAddressSpace {
+    pub fn new() -> Result<Self, &'static str> {
+        // the tables are too big for the stack, and all-zero is a valid, not yet initialized table
+        let mut tables = unsafe {
+            let ptr = alloc_zeroed(Layout::new::<UserTranslationTable>()) as *mut UserTranslationTable;
+
+            if ptr.is_null() {
+                return Err("out of memory for user translation tables");
+            }
+
+            Box::from_raw(ptr)
+        };
+
+        tables.init()?;
+        let phys_tables_base_addr = tables.phys_base_address()?;
+
+        Ok(Self {
+            tables,
+            phys_tables_base_addr,
+            regions: Vec::new(),
+        })
+    }
+
+    /// map fresh zeroed memory at `virt_region`, accessible from EL0
+    pub fn map_anonymous(&mut self, virt_region: &MemoryRegion<Virtual>, access_permissions: AccessPermissions, execute_never: bool) -> Result<(), &'static str> {
+        if self.regions.iter().any(|region| region.virt_region.overlaps(virt_region)) {
+            return Err("user region overlaps an existing one");
+        }
+
+        // dropped again if mapping it fails
+        let region = UserRegion::new(virt_region, execute_never)?;
+
+        let attr = AttributeFields {
+            mem_attributes: MemAttributes::CacheableDRAM,
+            access_permissions,
+            execute_never,
+            user_accessible: true,
+        };
+
+        unsafe { self.tables.map_at(virt_region, &region.phys_region, &attr)? };
+
+        self.regions.push(region);
+
+        Ok(())
+    }
+
+    /// the kernel's view of `len` bytes of user memory at `virt_addr`, which must lie within a
+    /// single region
+    fn kernel_ptr(&self, virt_addr: Address<Virtual>, len: usize) -> Result<(*mut u8, &UserRegion), &'static str> {
+        let region = self.regions.iter()
+            .find(|region| region.virt_region.contains(virt_addr))
+            .ok_or("access to unmapped user memory")?;
+
+        let offset = (virt_addr - region.virt_region.start_addr()).as_usize();
+        if len > region.virt_region.size() - offset {
+            return Err("access crosses the end of a user region");
+        }
+
+        Ok(((region.kernel_virt_region.start_addr().as_usize() + offset) as *mut u8, region))
+    }
+
+    /// copy `data` to `virt_addr` through the kernel's view of the backing memory, so that
+    /// read-only and not yet active regions can be filled as well
+    pub fn write(&mut self, virt_addr: Address<Virtual>, data: &[u8]) -> Result<(), &'static str> {
+        let (dst, region) = self.kernel_ptr(virt_addr, data.len())?;
+
+        unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len()) };
+
+        // user space fetches the instructions through a different virtual address
+        if !region.execute_never {
+            cpu::sync_instruction_cache(Address::new(dst as usize), data.len());
+        }
+
+        Ok(())
+    }
+
+    /// make this the address space seen by EL0 on the current core
+    ///
+    /// # safety
+    /// - the address space must not be dropped while it is active on any core
+    pub unsafe fn activate(&self) {
+        arch_mmu::mmu().set_user_translation_tables(Some(self.phys_tables_base_addr));
+    }
+}
+
+/// leave the current core without a user address space
+pub fn deactivate_user_address_space() {
+    unsafe { arch_mmu::mmu().set_user_translation_tables(None) };
+}
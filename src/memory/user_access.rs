@@ -0,0 +1,63 @@
+#[cfg(target_arch = "aarch64")]
+#[path = "../arch/aarch64/memory/user_access.rs"]
+mod arch_user_access;
+
+use crate::{bsp::memory::mmu::UserVirtAddrSpace, memory::{Address, Virtual}};
+
+/// `len` bytes at `addr` must lie within the user half of the address space, so that user space
+/// can't have the kernel access its own memory on its behalf
+fn check_user_range(addr: Address<Virtual>, len: usize) -> Result<(), &'static str> {
+    match addr.as_usize().checked_add(len) {
+        Some(end_exclusive) if end_exclusive <= UserVirtAddrSpace::SIZE => Ok(()),
+        _ => Err("address range is outside of user space"),
+    }
+}
+
+/// copy `dst.len()` bytes from user space at `src` in the address space active on this core,
+/// with the permissions of user space
+pub fn copy_from_user(dst: &mut [u8], src: Address<Virtual>) -> Result<(), &'static str> {
+    check_user_range(src, dst.len())?;
+
+    match unsafe { arch_user_access::copy_from_user(dst.as_mut_ptr(), src.as_usize(), dst.len()) } {
+        0 => Ok(()),
+        _ => Err("user memory not readable"),
+    }
+}
+
+/// copy `src` to user space at `dst` in the address space active on this core, with the
+/// permissions of user space
+#[allow(unused)]
+pub fn copy_to_user(dst: Address<Virtual>, src: &[u8]) -> Result<(), &'static str> {
+    check_user_range(dst, src.len())?;
+
+    match unsafe { arch_user_access::copy_to_user(dst.as_usize(), src.as_ptr(), src.len()) } {
+        0 => Ok(()),
+        _ => Err("user memory not writable"),
+    }
+}
+
+/// read the aligned 32 bit word at any kernel address, failing instead of faulting if nothing is
+/// mapped there
+pub fn probe_read(addr: Address<Virtual>) -> Result<u32, &'static str> {
+    if addr.as_usize() % core::mem::size_of::<u32>() != 0 {
+        return Err("address not aligned to 32 bit");
+    }
+
+    arch_user_access::probe_read_u32(addr.as_usize()).ok_or("address not readable")
+}
+
+/// write the aligned 32 bit word at any kernel address, failing instead of faulting if nothing
+/// writable is mapped there
+///
+/// # safety
+/// - the write must not break whatever owns the memory or device behind `addr`
+pub unsafe fn probe_write(addr: Address<Virtual>, value: u32) -> Result<(), &'static str> {
+    if addr.as_usize() % core::mem::size_of::<u32>() != 0 {
+        return Err("address not aligned to 32 bit");
+    }
+
+    match arch_user_access::probe_write_u32(addr.as_usize(), value) {
+        true => Ok(()),
+        false => Err("address not writable"),
+    }
+}
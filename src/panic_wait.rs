@@ -1,4 +1,4 @@
-use crate::{exception, cpu, error};
+use crate::{backtrace::Backtrace, console, exception, cpu, error, synchronization};
 use core::panic::PanicInfo;
 
 fn panic_prevent_reenter() {
@@ -24,6 +24,9 @@ fn panic(info: &PanicInfo) -> ! {
 
     panic_prevent_reenter();
 
+    // the locks on the way to the console may be held by the code that panicked
+    synchronization::enter_panic_mode();
+
     let (location, line, column) = match info.location() {
         Some(loc) => (loc.file(), loc.line(), loc.column()),
         _ => ("???", 0, 0)
@@ -35,6 +38,11 @@ fn panic(info: &PanicInfo) -> ! {
     error!("    in file {}:", location);
     error!("");
     error!("    {}", info.message());
+    error!("");
+    error!("backtrace:\n{}", Backtrace::current());
+
+    // IRQs are masked, so nothing drains the console's buffers anymore
+    console::console().flush();
 
     cpu::wait_forever()
 }
@@ -21,102 +21,44 @@ macro_rules! println {
     })
 }
 
-/// Prints an info, with a newline
+/// Logs a record of the given level, tagged with the calling module
 #[macro_export]
-macro_rules! info {
-    ($string:expr) => ({
-        let timestamp = $crate::time::time_manager().uptime();
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => ({
+        let level = $level;
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[I {:>3}.{:06}] ", $string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-        ));
+        if $crate::log::enabled(level, module_path!()) {
+            $crate::log::_log(level, module_path!(), format_args!($($arg)*));
+        }
     });
-    ($format_string:expr, $($arg:tt)*) => ({
-        let timestamp = $crate::time::time_manager().uptime();
+}
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[I {:>3}.{:06}] ", $format_string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-            $($arg)*
-        ));
-    })
+/// Logs an error
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Error, $($arg)*));
 }
 
-/// Prints a warning, with a newline
+/// Logs a warning
 #[macro_export]
 macro_rules! warn {
-    ($string:expr) => ({
-        let timestamp = $crate::time::time_manager().uptime();
-
-        $crate::print::_print(format_args_nl!(
-            concat!("[W {:>3}.{:06}] ", $string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-        ));
-    });
-    ($format_string:expr, $($arg:tt)*) => ({
-        let timestamp = $crate::time::time_manager().uptime();
-
-        $crate::print::_print(format_args_nl!(
-            concat!("[W {:>3}.{:06}] ", $format_string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-            $($arg)*
-        ));
-    })
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Warn, $($arg)*));
 }
 
-/// Prints an error, with a newline
+/// Logs an info
 #[macro_export]
-macro_rules! error {
-    ($string:expr) => ({
-        let timestamp = $crate::time::time_manager().uptime();
-
-        $crate::print::_print(format_args_nl!(
-            concat!("[E {:>3}.{:06}] ", $string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-        ));
-    });
-    ($format_string:expr, $($arg:tt)*) => ({
-        let timestamp = $crate::time::time_manager().uptime();
-
-        $crate::print::_print(format_args_nl!(
-            concat!("[E {:>3}.{:06}] ", $format_string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-            $($arg)*
-        ));
-    })
+macro_rules! info {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Info, $($arg)*));
 }
 
-/// Prints a debug message only shown when using the feature "debug_prints", with a newline
+/// Logs a debug message, shown by default when using the feature "debug_prints"
 #[macro_export]
 macro_rules! debug {
-    ($string:expr) => ({
-        if cfg!(feature = "debug_prints") {
-            let timestamp = $crate::time::time_manager().uptime();
-
-            $crate::print::_print(format_args_nl!(
-                concat!("[D {:>3}.{:06}] ", $string),
-                timestamp.as_secs(),
-                timestamp.subsec_micros(),
-            ));
-        }
-    });
-    ($format_string:expr, $($arg:tt)*) => ({
-        if cfg!(feature = "debug_prints") {
-            let timestamp = $crate::time::time_manager().uptime();
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Debug, $($arg)*));
+}
 
-            $crate::print::_print(format_args_nl!(
-                concat!("[D {:>3}.{:06}] ", $format_string),
-                timestamp.as_secs(),
-                timestamp.subsec_micros(),
-                $($arg)*
-            ));
-        }
-    });
+/// Logs a trace message, only shown for modules whose maximum level is raised at runtime
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Trace, $($arg)*));
 }
@@ -0,0 +1,439 @@
+#[cfg(target_arch = "aarch64")]
+#[path = "arch/aarch64/sched.rs"]
+mod arch_sched;
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
+use core::{mem, num::NonZeroUsize, sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+
+use crate::{bsp::{self, memory::mmu::KernelGranule}, cpu, exception::{stack_guard, ExceptionContext}, info, memory::{mmu::{self, MemoryRegion, UserAddressSpace}, Address, Physical, Virtual}, synchronization::{interface::Mutex, IRQSafeSpinLock}, time, warn};
+
+pub use arch_sched::*;
+
+pub type ThreadId = usize;
+
+/// how long a thread may run before it gets preempted
+const TIME_SLICE: Duration = Duration::from_millis(10);
+
+const THREAD_STACK_SIZE: usize = 64 * 1024;
+
+const KERNEL_STACK_NAME: &str = "Kernel stack";
+
+/// the kernel stack of a thread, mapped from frames with an unmapped guard page below it
+struct KernelStack {
+    phys_region: MemoryRegion<Physical>,
+    virt_region: MemoryRegion<Virtual>,
+    guard_region: MemoryRegion<Virtual>,
+}
+
+struct Thread {
+    id: ThreadId,
+    name: &'static str,
+
+    /// `None` for the thread a core was already running when the scheduler was started on it
+    stack: Option<KernelStack>,
+
+    /// the saved context, only valid while the thread is not running
+    context: *mut ExceptionContext,
+
+    /// the closure run by the thread, taken once the thread starts
+    entry: Option<Box<dyn FnOnce() + Send>>,
+
+    /// `Some` for threads running in user space
+    address_space: Option<Arc<UserAddressSpace>>,
+
+    /// set by a `wake()` that came while the thread wasn't blocked, its next `park()` returns
+    /// right away
+    wake_pending: bool,
+}
+
+struct CoreScheduler {
+    current: Option<Box<Thread>>,
+    run_queue: VecDeque<Box<Thread>>,
+
+    /// an exited thread whose stack cannot be freed yet, since it is still in use while switching
+    /// away from it
+    zombie: Option<Box<Thread>>,
+
+    /// threads waiting for a timeout or `wake()` to wake them up
+    sleeping: VecDeque<Box<Thread>>,
+
+    need_resched: bool,
+    current_disposition: Disposition,
+}
+
+/// what happens to the current thread when it is switched away from
+#[derive(Copy, Clone, PartialEq)]
+enum Disposition {
+    Requeue,
+    Exit,
+    Sleep,
+}
+
+/// every core only ever runs the threads of its own run queue, so a thread's stack is never
+/// picked up by another core while the switch away from it is still in progress
+static CORE_SCHEDULERS: [IRQSafeSpinLock<CoreScheduler>; bsp::cpu::NUM_CORES] = [const { IRQSafeSpinLock::new(CoreScheduler::new()) }; bsp::cpu::NUM_CORES];
+
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
+
+// threads only move between cores when they are spawned, so the raw context pointer may be sent
+unsafe impl Send for Thread {}
+
+impl Thread {
+    fn new(name: &'static str, stack: Option<KernelStack>, entry: Option<Box<dyn FnOnce() + Send>>) -> Self {
+        Self {
+            id: NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            stack,
+            context: core::ptr::null_mut(),
+            entry,
+            address_space: None,
+            wake_pending: false,
+        }
+    }
+
+    fn shares_address_space(&self, other: &Thread) -> bool {
+        match (&self.address_space, &other.address_space) {
+            (None, None) => true,
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl KernelStack {
+    /// a fault in the guard page is reported as an overflow of the stack called `name`
+    fn new(name: &'static str) -> Result<Self, &'static str> {
+        let num_pages = NonZeroUsize::new(THREAD_STACK_SIZE.div_ceil(KernelGranule::SIZE)).unwrap();
+        let phys_region = mmu::kernel_alloc_frames(num_pages)?;
+
+        let (virt_region, guard_region) = match unsafe { mmu::kernel_map_stack(KERNEL_STACK_NAME, &phys_region) } {
+            Ok(x) => x,
+            Err(x) => {
+                mmu::kernel_free_frames(&phys_region)?;
+                return Err(x);
+            }
+        };
+
+        // dropping the stack on failure unmaps it again
+        let stack = Self { phys_region, virt_region, guard_region };
+        stack_guard::register(name, guard_region)?;
+
+        Ok(stack)
+    }
+
+    fn end_exclusive(&self) -> usize {
+        self.virt_region.end_exclusive_page_addr().into_inner().as_usize()
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        stack_guard::unregister(&self.guard_region);
+
+        // the thread is gone, so nothing uses the stack anymore
+        if let Err(x) = unsafe { mmu::kernel_unmap_stack(KERNEL_STACK_NAME, &self.virt_region, &self.guard_region) } {
+            warn!("failed to unmap a kernel stack: {}", x);
+            return;
+        }
+
+        if let Err(x) = mmu::kernel_free_frames(&self.phys_region) {
+            warn!("failed to free a kernel stack: {}", x);
+        }
+    }
+}
+
+impl CoreScheduler {
+    const fn new() -> Self {
+        Self {
+            current: None,
+            run_queue: VecDeque::new(),
+            zombie: None,
+            sleeping: VecDeque::new(),
+            need_resched: false,
+            current_disposition: Disposition::Requeue,
+        }
+    }
+
+    fn is_started(&self) -> bool {
+        self.current.is_some()
+    }
+
+    fn num_threads(&self) -> usize {
+        self.run_queue.len() + usize::from(self.is_started())
+    }
+
+    /// put the current thread aside and continue with the next runnable one
+    fn switch(&mut self, context: *mut ExceptionContext) -> *mut ExceptionContext {
+        self.need_resched = false;
+
+        // the previous switch moved away from the zombie's stack, so it can be freed now
+        self.zombie = None;
+
+        // the initial thread of a core can neither exit nor sleep, so it is always queued when the
+        // current thread leaves the run queue
+        let next = match self.run_queue.pop_front() {
+            None => return context,
+            Some(thread) => thread,
+        };
+
+        let mut prev = self.current.replace(next).expect("scheduler not started on this core");
+        prev.context = context;
+
+        let next = self.current.as_ref().unwrap();
+        if !prev.shares_address_space(next) {
+            match &next.address_space {
+                None => mmu::deactivate_user_address_space(),
+                // the address space lives at least as long as the thread that runs in it
+                Some(address_space) => unsafe { address_space.activate() },
+            }
+        }
+
+        match mem::replace(&mut self.current_disposition, Disposition::Requeue) {
+            Disposition::Requeue => self.run_queue.push_back(prev),
+            Disposition::Exit => self.zombie = Some(prev),
+            Disposition::Sleep => self.sleeping.push_back(prev),
+        }
+
+        self.current.as_ref().unwrap().context
+    }
+}
+
+fn core_scheduler() -> &'static IRQSafeSpinLock<CoreScheduler> {
+    &CORE_SCHEDULERS[cpu::smp::core_id::<usize>()]
+}
+
+extern "C" fn thread_start() -> ! {
+    let entry = core_scheduler().lock(|sched| sched.current.as_mut().and_then(|thread| thread.entry.take()));
+
+    if let Some(entry) = entry {
+        entry();
+    }
+
+    exit();
+}
+
+/// start scheduling on the current core, the code that is currently running becomes a thread
+/// called `name`
+///
+/// # safety
+/// - must be called once per core, with IRQs masked and the core's timer IRQ enabled
+pub unsafe fn init_core(name: &'static str) {
+    core_scheduler().lock(|sched| {
+        assert!(!sched.is_started(), "scheduler already started on this core");
+
+        sched.current = Some(Box::new(Thread::new(name, None, None)));
+    });
+
+    time::time_manager().set_interval(TIME_SLICE, timer_tick);
+}
+
+/// spawn a kernel thread on the started core with the fewest threads
+pub fn spawn(name: &'static str, f: impl FnOnce() + Send + 'static) -> ThreadId {
+    spawn_on(least_loaded_core(), name, f)
+}
+
+/// spawn a kernel thread that always runs on core `core_id`
+pub fn spawn_on(core_id: usize, name: &'static str, f: impl FnOnce() + Send + 'static) -> ThreadId {
+    let thread = Thread::new(name, None, Some(Box::new(f)));
+
+    spawn_thread(core_id, thread, ExceptionContext::new_kernel_thread(thread_start))
+}
+
+/// spawn a thread that starts executing `entry` at EL0 inside of `address_space`
+pub fn spawn_user(name: &'static str, address_space: Arc<UserAddressSpace>, entry: Address<Virtual>, stack_end_exclusive: Address<Virtual>) -> ThreadId {
+    let mut thread = Thread::new(name, None, None);
+    thread.address_space = Some(address_space);
+
+    spawn_thread(least_loaded_core(), thread, ExceptionContext::new_user_thread(entry, stack_end_exclusive))
+}
+
+fn least_loaded_core() -> usize {
+    (0..bsp::cpu::NUM_CORES)
+        .filter_map(|id| CORE_SCHEDULERS[id].lock(|sched| sched.is_started().then(|| (sched.num_threads(), id))))
+        .min()
+        .map(|(_, id)| id)
+        .expect("scheduler not started on any core")
+}
+
+/// give `thread` its own kernel stack, with `context` on top, and queue it on core `core_id`
+fn spawn_thread(core_id: usize, mut thread: Thread, context: ExceptionContext) -> ThreadId {
+    assert!(core_id < bsp::cpu::NUM_CORES, "invalid core id");
+
+    let stack = KernelStack::new(thread.name).expect("failed to allocate a kernel stack");
+
+    // place the initial context on top of the new stack, restoring it starts the thread. for user
+    // threads, exceptions from EL0 later use the stack starting right there as well
+    let context_ptr = (stack.end_exclusive() - mem::size_of::<ExceptionContext>()) as *mut ExceptionContext;
+    unsafe { context_ptr.write(context) };
+
+    thread.stack = Some(stack);
+    thread.context = context_ptr;
+
+    let id = thread.id;
+    CORE_SCHEDULERS[core_id].lock(|sched| sched.run_queue.push_back(Box::new(thread)));
+
+    id
+}
+
+/// terminate the current thread
+pub fn exit() -> ! {
+    core_scheduler().lock(|sched| {
+        let current = sched.current.as_ref().expect("scheduler not started on this core");
+        assert!(current.stack.is_some(), "the initial thread of a core cannot exit");
+
+        sched.current_disposition = Disposition::Exit;
+    });
+
+    yield_now();
+
+    unreachable!("exited thread was scheduled again");
+}
+
+pub fn current_thread_id() -> Option<ThreadId> {
+    core_scheduler().lock(|sched| sched.current.as_ref().map(|thread| thread.id))
+}
+
+/// runs every time slice in IRQ context
+fn timer_tick() {
+    core_scheduler().lock(|sched| sched.need_resched = true);
+}
+
+/// switch to the next thread if the current one used up its time slice, called on the way out of
+/// an IRQ with the context of the interrupted thread
+pub fn preempt(context: *mut ExceptionContext) -> *mut ExceptionContext {
+    core_scheduler().lock(|sched| {
+        if !sched.need_resched {
+            return context;
+        }
+
+        sched.switch(context)
+    })
+}
+
+/// terminate the current thread from exception context and switch to the next one
+pub fn kill_current(context: *mut ExceptionContext) -> *mut ExceptionContext {
+    core_scheduler().lock(|sched| {
+        let current = sched.current.as_ref().expect("scheduler not started on this core");
+        assert!(current.stack.is_some(), "the initial thread of a core cannot be killed");
+
+        sched.current_disposition = Disposition::Exit;
+        sched.switch(context)
+    })
+}
+
+/// put the current thread to sleep for `duration` and switch to the next one
+pub fn sleep_current(context: *mut ExceptionContext, duration: Duration) -> *mut ExceptionContext {
+    let id = core_scheduler().lock(|sched| {
+        let current = sched.current.as_ref().expect("scheduler not started on this core");
+        assert!(current.stack.is_some(), "the initial thread of a core cannot sleep");
+
+        sched.current_disposition = Disposition::Sleep;
+        current.id
+    });
+
+    // timeouts run on the core that set them, which is the one the thread is bound to. IRQs are
+    // masked until the switch is done, so the wake-up cannot overtake it
+    time::time_manager().set_timeout(duration, move || wake(id));
+
+    core_scheduler().lock(|sched| sched.switch(context))
+}
+
+/// block the current kernel thread until `wake()` is called for it, returns right away if that
+/// already happened since the last `park()`
+///
+/// may also return spuriously, so the condition waited for has to be checked again. a core's
+/// initial thread cannot block, it waits for the next IRQ instead
+pub fn park() {
+    let can_block = core_scheduler().lock(|sched| sched.current.as_ref().is_some_and(|thread| thread.stack.is_some()));
+
+    if can_block {
+        park_now();
+    } else {
+        cpu::wfi();
+    }
+}
+
+/// block the current kernel thread for at least `duration`
+///
+/// before the scheduler is started on the current core, this idles the core instead
+pub fn sleep(duration: Duration) {
+    let id = match current_thread_id() {
+        None => return time::time_manager().sleep(duration),
+        Some(id) => id,
+    };
+
+    let deadline = time::time_manager().uptime() + duration;
+    time::time_manager().set_timeout(duration, move || wake(id));
+
+    while time::time_manager().uptime() < deadline {
+        park();
+    }
+}
+
+/// block the current thread unless a wake-up is pending, called when the current thread parks
+pub fn park_current(context: *mut ExceptionContext) -> *mut ExceptionContext {
+    core_scheduler().lock(|sched| {
+        let current = sched.current.as_mut().expect("scheduler not started on this core");
+        assert!(current.stack.is_some(), "the initial thread of a core cannot park");
+
+        if mem::take(&mut current.wake_pending) {
+            return context;
+        }
+
+        sched.current_disposition = Disposition::Sleep;
+        sched.switch(context)
+    })
+}
+
+/// make the thread `id` runnable again if it is blocked, otherwise its next `park()` returns
+/// right away
+///
+/// may be called from IRQ context on any core, a thread blocked on another core resumes at that
+/// core's next time slice at the latest
+pub fn wake(id: ThreadId) {
+    for core_scheduler in CORE_SCHEDULERS.iter() {
+        let found = core_scheduler.lock(|sched| {
+            if let Some(thread) = sched.sleeping.iter().position(|thread| thread.id == id).and_then(|index| sched.sleeping.remove(index)) {
+                sched.run_queue.push_back(thread);
+                sched.need_resched = true;
+
+                return true;
+            }
+
+            match sched.current.iter_mut().chain(sched.run_queue.iter_mut()).find(|thread| thread.id == id) {
+                None => false,
+                Some(thread) => {
+                    thread.wake_pending = true;
+                    true
+                }
+            }
+        });
+
+        if found {
+            return;
+        }
+    }
+}
+
+/// switch to the next thread, called when the current thread yields
+pub fn reschedule(context: *mut ExceptionContext) -> *mut ExceptionContext {
+    core_scheduler().lock(|sched| sched.switch(context))
+}
+
+pub fn print_threads() {
+    for (core_id, core_scheduler) in CORE_SCHEDULERS.iter().enumerate() {
+        core_scheduler.lock(|sched| {
+            if let Some(current) = &sched.current {
+                info!("    core {}: {: >3}. {} (running)", core_id, current.id, current.name);
+            }
+
+            for thread in sched.run_queue.iter() {
+                info!("    core {}: {: >3}. {}", core_id, thread.id, thread.name);
+            }
+
+            for thread in sched.sleeping.iter() {
+                info!("    core {}: {: >3}. {} (sleeping)", core_id, thread.id, thread.name);
+            }
+        });
+    }
+}
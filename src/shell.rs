@@ -0,0 +1,109 @@
+mod commands;
+mod line_editor;
+
+use alloc::vec::Vec;
+
+use line_editor::LineEditor;
+
+use crate::{console, print, println, sched, synchronization::{interface::ReadWriteEx, InitStateLock}};
+
+pub mod interface {
+    pub trait Command {
+        /// the word the command is invoked with
+        fn name(&self) -> &'static str;
+
+        /// the arguments as shown by `help`, empty for commands that take none
+        fn usage(&self) -> &'static str {
+            ""
+        }
+
+        /// a single line shown by `help`
+        fn description(&self) -> &'static str;
+
+        fn run(&self, args: &[&str]) -> Result<(), &'static str>;
+    }
+}
+
+const PROMPT: &str = "starlight> ";
+
+pub struct ShellManager {
+    commands: InitStateLock<Vec<&'static (dyn interface::Command + Sync)>>,
+}
+
+static SHELL_MANAGER: ShellManager = ShellManager::new();
+
+pub fn shell_manager() -> &'static ShellManager {
+    &SHELL_MANAGER
+}
+
+impl ShellManager {
+    pub const fn new() -> Self {
+        Self {
+            commands: InitStateLock::new(Vec::new()),
+        }
+    }
+
+    /// make `command` available in the shell, names registered first win
+    pub fn register_command(&self, command: &'static (dyn interface::Command + Sync)) {
+        self.commands.write(|commands| commands.push(command));
+    }
+
+    fn find_command(&self, name: &str) -> Option<&'static (dyn interface::Command + Sync)> {
+        self.commands.read(|commands| commands.iter().find(|command| command.name() == name).copied())
+    }
+
+    fn print_commands(&self) {
+        self.commands.read(|commands| {
+            for command in commands {
+                let invocation = [command.name(), command.usage()].join(" ");
+                println!("    {:<24} {}", invocation.trim_end(), command.description());
+            }
+        })
+    }
+
+    fn execute(&self, line: &str) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let (name, args) = match words.split_first() {
+            None => return,
+            Some(x) => x,
+        };
+
+        match self.find_command(name) {
+            None => println!("unknown command: {}, try help", name),
+            Some(command) => {
+                if let Err(x) = command.run(args) {
+                    println!("{}: {}", name, x);
+                }
+            }
+        }
+    }
+
+    /// read and execute lines forever
+    fn run(&self) -> ! {
+        let mut editor = LineEditor::new();
+
+        print!("{}", PROMPT);
+
+        loop {
+            if let Some(line) = editor.handle_char(console::console().read_char()) {
+                self.execute(&line);
+
+                print!("{}", PROMPT);
+            }
+        }
+    }
+}
+
+/// register the built-in commands
+///
+/// # safety
+/// - must only be called during kernel init
+pub unsafe fn init() {
+    commands::register_builtin_commands();
+}
+
+/// start the shell in a kernel thread of its own
+pub fn spawn() {
+    sched::spawn("shell", || shell_manager().run());
+}
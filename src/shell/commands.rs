@@ -0,0 +1,381 @@
+use super::{interface::Command, shell_manager};
+use crate::{bsp, chainload, console, driver, elf, exception, framebuffer::{self, Color}, log, memory::{self, Address}, print, println, time};
+
+/// the most words `peek` dumps at once
+const MAX_PEEK_WORDS: usize = 256;
+
+/// prints a greeting and exits, built from `hello.s`
+static HELLO_ELF: &[u8] = include_bytes!("hello.elf");
+
+struct Help;
+struct Mappings;
+struct Drivers;
+struct Irqs;
+struct Heap;
+struct Uptime;
+struct El;
+struct Log;
+struct Dmesg;
+struct Peek;
+struct Poke;
+struct Reboot;
+struct Chainload;
+struct Board;
+struct Fb;
+struct Run;
+
+/// decimal, or hexadecimal with a `0x` prefix
+fn parse_number(arg: &str) -> Result<usize, &'static str> {
+    let result = match arg.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => arg.parse(),
+    };
+
+    result.map_err(|_| "invalid number")
+}
+
+fn expect_args<'a, const N: usize>(args: &[&'a str]) -> Result<[&'a str; N], &'static str> {
+    args.try_into().map_err(|_| "wrong number of arguments")
+}
+
+/// show color bars until a key is pressed
+fn show_test_pattern(framebuffer: &dyn framebuffer::interface::Framebuffer) -> Result<(), &'static str> {
+    const BARS: [Color; 8] = [Color::WHITE, Color::new(0xFF, 0xFF, 0x00), Color::new(0x00, 0xFF, 0xFF), Color::GREEN, Color::new(0xFF, 0x00, 0xFF), Color::RED, Color::BLUE, Color::BLACK];
+
+    let info = framebuffer.info();
+
+    let mut result = Ok(());
+    framebuffer.draw(&mut |surface| {
+        let bar_width = info.width.div_ceil(BARS.len());
+
+        for (i, color) in BARS.iter().enumerate() {
+            surface.fill_rect(i * bar_width, 0, bar_width, info.height, *color);
+        }
+
+        if BARS.iter().enumerate().any(|(i, color)| surface.pixel(i * bar_width, info.height / 2) != Some(color.quantized(info.depth))) {
+            result = Err("pixels don't read back as drawn");
+        }
+    });
+
+    result?;
+    framebuffer.flip()?;
+
+    console::console().read_char();
+
+    Ok(())
+}
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn description(&self) -> &'static str {
+        "list all commands"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        shell_manager().print_commands();
+        Ok(())
+    }
+}
+
+impl Command for Mappings {
+    fn name(&self) -> &'static str {
+        "mappings"
+    }
+
+    fn description(&self) -> &'static str {
+        "print the kernel's virtual memory mappings"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        memory::mmu::kernel_print_mappings();
+        Ok(())
+    }
+}
+
+impl Command for Drivers {
+    fn name(&self) -> &'static str {
+        "drivers"
+    }
+
+    fn description(&self) -> &'static str {
+        "list the loaded drivers"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        driver::driver_manager().enumerate();
+        Ok(())
+    }
+}
+
+impl Command for Irqs {
+    fn name(&self) -> &'static str {
+        "irqs"
+    }
+
+    fn description(&self) -> &'static str {
+        "list the registered IRQ handlers"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        exception::asynchronous::irq_manager().print_handler();
+        Ok(())
+    }
+}
+
+impl Command for Heap {
+    fn name(&self) -> &'static str {
+        "heap"
+    }
+
+    fn description(&self) -> &'static str {
+        "print the kernel heap usage"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        memory::heap_alloc::kernel_heap_allocator().print_usage();
+        Ok(())
+    }
+}
+
+impl Command for Uptime {
+    fn name(&self) -> &'static str {
+        "uptime"
+    }
+
+    fn description(&self) -> &'static str {
+        "print the time since boot"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        let uptime = time::time_manager().uptime();
+        println!("{}.{:06} s", uptime.as_secs(), uptime.subsec_micros());
+        Ok(())
+    }
+}
+
+impl Command for El {
+    fn name(&self) -> &'static str {
+        "el"
+    }
+
+    fn description(&self) -> &'static str {
+        "print the current privilege level"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        let (_, privilege_level) = exception::current_privilege_level();
+        println!("{}", privilege_level);
+        Ok(())
+    }
+}
+
+impl Command for Log {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[<level>|clear] [module]"
+    }
+
+    fn description(&self) -> &'static str {
+        "show or change the maximum log level, globally or for a module"
+    }
+
+    fn run(&self, args: &[&str]) -> Result<(), &'static str> {
+        match args {
+            [] => {
+                println!("global: {}", log::max_level());
+                log::for_each_module_filter(|module_path, max_level| println!("{}: {}", module_path, max_level));
+            }
+            ["clear", module_path] => log::clear_module_max_level(module_path),
+            [max_level] => log::set_max_level(max_level.parse()?),
+            [max_level, module_path] => log::set_module_max_level(module_path, max_level.parse()?)?,
+            _ => return Err("wrong number of arguments"),
+        }
+
+        Ok(())
+    }
+}
+
+impl Command for Dmesg {
+    fn name(&self) -> &'static str {
+        "dmesg"
+    }
+
+    fn description(&self) -> &'static str {
+        "print the kernel log since boot"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        log::dmesg::replay(log::console_sink());
+        Ok(())
+    }
+}
+
+impl Command for Peek {
+    fn name(&self) -> &'static str {
+        "peek"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<addr> [words]"
+    }
+
+    fn description(&self) -> &'static str {
+        "dump 32 bit words of kernel memory"
+    }
+
+    fn run(&self, args: &[&str]) -> Result<(), &'static str> {
+        let (addr, num_words) = match args {
+            [addr] => (parse_number(addr)?, 1),
+            [addr, num_words] => (parse_number(addr)?, parse_number(num_words)?),
+            _ => return Err("wrong number of arguments"),
+        };
+
+        if num_words > MAX_PEEK_WORDS {
+            return Err("too many words");
+        }
+
+        for line_start in (0..num_words).step_by(4) {
+            let line_addr = addr + line_start * 4;
+            print!("{:#018x}:", line_addr);
+
+            for i in line_start..num_words.min(line_start + 4) {
+                let word = memory::user_access::probe_read(Address::new(addr + i * 4))?;
+                print!(" {:08x}", word);
+            }
+
+            println!();
+        }
+
+        Ok(())
+    }
+}
+
+impl Command for Poke {
+    fn name(&self) -> &'static str {
+        "poke"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<addr> <value>"
+    }
+
+    fn description(&self) -> &'static str {
+        "write a 32 bit word of kernel memory"
+    }
+
+    fn run(&self, args: &[&str]) -> Result<(), &'static str> {
+        let [addr, value] = expect_args(args)?;
+
+        let value = u32::try_from(parse_number(value)?).map_err(|_| "value exceeds 32 bit")?;
+
+        // whoever types it is in charge of the consequences
+        unsafe { memory::user_access::probe_write(Address::new(parse_number(addr)?), value) }
+    }
+}
+
+impl Command for Reboot {
+    fn name(&self) -> &'static str {
+        "reboot"
+    }
+
+    fn description(&self) -> &'static str {
+        "reset the board"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        println!("rebooting");
+        console::console().flush();
+
+        bsp::driver::reboot()
+    }
+}
+
+impl Command for Chainload {
+    fn name(&self) -> &'static str {
+        "chainload"
+    }
+
+    fn description(&self) -> &'static str {
+        "receive a kernel image from the host and boot it"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        println!("waiting for the host to send an image");
+
+        chainload::chainload().map(|_| ())
+    }
+}
+
+impl Command for Board {
+    fn name(&self) -> &'static str {
+        "board"
+    }
+
+    fn description(&self) -> &'static str {
+        "print the board information reported by the firmware"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        println!("{}:", bsp::board_name());
+        bsp::print_board_info()
+    }
+}
+
+impl Command for Fb {
+    fn name(&self) -> &'static str {
+        "fb"
+    }
+
+    fn description(&self) -> &'static str {
+        "draw a test pattern on the display until a key is pressed, checking that it reads back"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        let framebuffer = framebuffer::framebuffer().ok_or("no framebuffer")?;
+        let info = framebuffer.info();
+
+        println!("{}x{}, {} bpp, pitch {} bytes, {} buffers", info.width, info.height, info.depth, info.pitch, info.num_buffers);
+        println!("press any key to continue");
+
+        // the console on the display would otherwise draw over the pattern with its next frame
+        let framebuffer_console = bsp::driver::framebuffer_console();
+        framebuffer_console.suspend();
+
+        let result = show_test_pattern(framebuffer);
+
+        framebuffer_console.resume();
+
+        result
+    }
+}
+
+impl Command for Run {
+    fn name(&self) -> &'static str {
+        "run"
+    }
+
+    fn description(&self) -> &'static str {
+        "start the embedded test program in user space"
+    }
+
+    fn run(&self, _args: &[&str]) -> Result<(), &'static str> {
+        let id = elf::load(HELLO_ELF)?.spawn("hello");
+        println!("started thread {}", id);
+
+        Ok(())
+    }
+}
+
+static BUILTIN_COMMANDS: [&(dyn Command + Sync); 16] = [&Help, &Mappings, &Drivers, &Irqs, &Heap, &Uptime, &El, &Log, &Dmesg, &Peek, &Poke, &Reboot, &Chainload, &Board, &Fb, &Run];
+
+pub fn register_builtin_commands() {
+    for command in BUILTIN_COMMANDS {
+        shell_manager().register_command(command);
+    }
+}
@@ -0,0 +1,22 @@
+// the test program started by the shell's `run` command, rebuild `hello.elf` with `make hello`
+
+.equ ABI_VERSION_1, 1
+
+.equ SYS_WRITE_CONSOLE, 0
+.equ SYS_EXIT, 1
+
+.section .text._start, "ax"
+.global _start
+_start:
+    adr     x0, message
+    mov     x1, message_end - message
+    mov     x8, SYS_WRITE_CONSOLE
+    svc     ABI_VERSION_1
+
+    mov     x0, 0
+    mov     x8, SYS_EXIT
+    svc     ABI_VERSION_1
+
+message:
+    .ascii  "hello from user space\n"
+message_end:
@@ -0,0 +1,72 @@
+use alloc::string::String;
+
+use crate::{console::{EscapeParser, Parsed}, print};
+
+/// the longest line that can be entered, further characters are ignored
+const MAX_LINE_LEN: usize = 256;
+
+const CTRL_C: char = '\x03';
+const BACKSPACE: char = '\x08';
+const CTRL_U: char = '\x15';
+const CTRL_W: char = '\x17';
+const DELETE: char = '\x7f';
+
+/// collects a line, echoing what is typed and handling the usual editing keys
+pub struct LineEditor {
+    line: String,
+    escape_parser: EscapeParser,
+}
+
+impl LineEditor {
+    pub const fn new() -> Self {
+        Self {
+            line: String::new(),
+            escape_parser: EscapeParser::new(),
+        }
+    }
+
+    fn erase_chars(&mut self, count: usize) {
+        for _ in 0..count {
+            self.line.pop();
+            print!("\x08 \x08");
+        }
+    }
+
+    /// feed a received character, returns the line once it is complete
+    pub fn handle_char(&mut self, c: char) -> Option<String> {
+        // cursor keys and friends are not supported, swallow their escape sequences
+        let c = match self.escape_parser.feed(c) {
+            Some(Parsed::Char(c)) => c,
+            _ => return None,
+        };
+
+        match c {
+            '\n' => {
+                print!("\n");
+                return Some(core::mem::take(&mut self.line));
+            }
+            BACKSPACE | DELETE => self.erase_chars(self.line.len().min(1)),
+            CTRL_U => self.erase_chars(self.line.len()),
+            CTRL_W => {
+                let trimmed_len = self.line.trim_end().len();
+                let word_start = self.line[..trimmed_len].rfind(' ').map_or(0, |i| i + 1);
+
+                self.erase_chars(self.line.len() - word_start);
+            }
+            CTRL_C => {
+                print!("^C\n");
+                self.line.clear();
+                return Some(String::new());
+            }
+            c if c.is_ascii_graphic() || c == ' ' => {
+                if self.line.len() < MAX_LINE_LEN {
+                    self.line.push(c);
+                    print!("{}", c);
+                }
+            }
+            _ => (),
+        }
+
+        None
+    }
+}
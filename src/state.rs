@@ -44,4 +44,10 @@ impl StateManager {
             panic!("transition_to_single_core_main() called while state != Init")
         }
     }
+
+    pub fn transition_to_multi_core_main(&self) {
+        if self.0.compare_exchange(Self::SINGLE_CORE_MAIN, Self::MULTI_CORE_MAIN, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            panic!("transition_to_multi_core_main() called while state != SingleCoreMain")
+        }
+    }
 }
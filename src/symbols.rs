@@ -0,0 +1,54 @@
+use core::{mem::size_of, slice, str};
+
+use crate::{bsp, memory::{Address, Virtual}};
+
+/// one function in the table the kernel symbols tool patches into the image after linking
+///
+/// the table starts with the number of symbols as `u64`, followed by the symbols sorted by start
+/// address and then the names they point into
+#[repr(C)]
+struct Symbol {
+    start: u64,
+    size: u32,
+    name_offset: u32,
+    name_len: u32,
+    _reserved: u32,
+}
+
+const HEADER_SIZE: usize = size_of::<u64>();
+
+fn table() -> (&'static [Symbol], &'static [u8]) {
+    let raw = bsp::memory::kernel_symbol_table();
+
+    let num_symbols = u64::from_le_bytes(raw[..HEADER_SIZE].try_into().unwrap()) as usize;
+    let symbols_size = num_symbols.saturating_mul(size_of::<Symbol>());
+
+    if symbols_size > raw.len() - HEADER_SIZE {
+        return (&[], &[]);
+    }
+
+    // the table is 8 byte aligned by the linker script
+    let symbols = unsafe { slice::from_raw_parts(raw[HEADER_SIZE..].as_ptr() as *const Symbol, num_symbols) };
+
+    (symbols, &raw[(HEADER_SIZE + symbols_size)..])
+}
+
+/// the demangled name of the function containing `addr`, and the offset of `addr` into it
+pub fn lookup(addr: Address<Virtual>) -> Option<(&'static str, usize)> {
+    let (symbols, names) = table();
+    let addr = addr.as_usize() as u64;
+
+    let index = symbols.partition_point(|symbol| symbol.start <= addr).checked_sub(1)?;
+    let symbol = &symbols[index];
+
+    let offset = addr - symbol.start;
+    if offset >= symbol.size as u64 {
+        return None;
+    }
+
+    let name = names
+        .get(symbol.name_offset as usize..)?
+        .get(..symbol.name_len as usize)?;
+
+    Some((str::from_utf8(name).ok()?, offset as usize))
+}
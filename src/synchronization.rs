@@ -1,4 +1,4 @@
-use core::cell::UnsafeCell;
+use core::{cell::UnsafeCell, hint, sync::atomic::{AtomicBool, AtomicUsize, Ordering}};
 
 use crate::{exception, state};
 
@@ -17,24 +17,184 @@ pub mod interface {
     }
 }
 
-pub struct IRQSafeNullLock<T> where T: ?Sized {
+/// how often a held lock is polled after the kernel panicked before it is taken anyway
+const PANIC_SPIN_LIMIT: usize = 1 << 20;
+
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// the raw lock word shared by `SpinLock` and `IRQSafeSpinLock`
+///
+/// on aarch64 the compare-exchange compiles down to an exclusive load/store pair, so the lock
+/// must only be used after the MMU and caches are enabled
+struct RawSpinLock {
+    locked: AtomicBool,
+}
+
+/// a mutex that spins until the lock is free
+///
+/// must not be used for data that is also accessed from IRQ context, use `IRQSafeSpinLock` instead
+pub struct SpinLock<T> where T: ?Sized {
+    lock: RawSpinLock,
+    data: UnsafeCell<T>,
+}
+
+/// a mutex that masks IRQs on the local core and then spins until the lock is free
+pub struct IRQSafeSpinLock<T> where T: ?Sized {
+    lock: RawSpinLock,
+    data: UnsafeCell<T>,
+}
+
+/// a reader-writer spinlock, allowing either any number of readers or a single writer
+///
+/// IRQs are masked on the local core while the lock is held, so that an IRQ handler reading the
+/// data cannot deadlock against a writer on the same core
+///
+/// the data lives at the start of the lock, so that data precomputed by tools at a symbol's
+/// address, like the kernel translation tables, can be wrapped as well
+#[repr(C)]
+pub struct RwLock<T> {
     data: UnsafeCell<T>,
+
+    /// number of active readers, or `WRITER` if a writer holds the lock
+    state: AtomicUsize,
 }
 
 pub struct InitStateLock<T> where T: ?Sized {
     data: UnsafeCell<T>,
 }
 
-unsafe impl<T> Send for IRQSafeNullLock<T> where T: ?Sized + Send {}
-unsafe impl<T> Sync for IRQSafeNullLock<T> where T: ?Sized + Send {}
+impl RawSpinLock {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    #[inline(always)]
+    fn acquire(&self) {
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            let mut spins = 0;
+
+            // only retry the exclusive store once the lock looks free again
+            while self.locked.load(Ordering::Relaxed) {
+                if PANICKING.load(Ordering::Relaxed) {
+                    spins += 1;
+
+                    if spins > PANIC_SPIN_LIMIT {
+                        return;
+                    }
+                }
+
+                hint::spin_loop();
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn try_acquire(&self) -> bool {
+        self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    #[inline(always)]
+    fn release(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+/// from now on, spinlocks that stay held are taken anyway after a while
+///
+/// the panicking core may have been interrupted while holding the console's or a driver's lock,
+/// which would otherwise swallow the panic message. this breaks mutual exclusion, so it must only
+/// be called by the panic handler
+pub fn enter_panic_mode() {
+    PANICKING.store(true, Ordering::Relaxed);
+}
+
+unsafe impl<T> Send for SpinLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for SpinLock<T> where T: ?Sized + Send {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            lock: RawSpinLock::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// like `lock()`, but returns `None` instead of waiting if the lock is held
+    pub fn try_lock<'a, R>(&'a self, f: impl FnOnce(&'a mut T) -> R) -> Option<R> {
+        if !self.lock.try_acquire() {
+            return None;
+        }
+
+        let data = unsafe { &mut *self.data.get() };
+        let ret = f(data);
+
+        self.lock.release();
+
+        Some(ret)
+    }
+}
+
+unsafe impl<T> Send for IRQSafeSpinLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for IRQSafeSpinLock<T> where T: ?Sized + Send {}
+
+impl<T> IRQSafeSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            lock: RawSpinLock::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+unsafe impl<T> Send for RwLock<T> where T: Send {}
+unsafe impl<T> Sync for RwLock<T> where T: Send + Sync {}
+
+impl<T> RwLock<T> {
+    const WRITER: usize = 1 << (usize::BITS - 1);
 
-impl<T> IRQSafeNullLock<T> {
-    #[allow(unused)]
     pub const fn new(data: T) -> Self {
         Self {
             data: UnsafeCell::new(data),
+            state: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline(always)]
+    fn acquire_write(&self) {
+        while self.state.compare_exchange_weak(0, Self::WRITER, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            while self.state.load(Ordering::Relaxed) != 0 {
+                hint::spin_loop();
+            }
         }
     }
+
+    #[inline(always)]
+    fn release_write(&self) {
+        self.state.store(0, Ordering::Release);
+    }
+
+    #[inline(always)]
+    fn acquire_read(&self) {
+        loop {
+            let readers = self.state.load(Ordering::Relaxed);
+
+            if readers & Self::WRITER != 0 {
+                hint::spin_loop();
+                continue;
+            }
+
+            if self.state.compare_exchange_weak(readers, readers + 1, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                return;
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn release_read(&self) {
+        self.state.fetch_sub(1, Ordering::Release);
+    }
 }
 
 unsafe impl<T> Send for InitStateLock<T> where T: ?Sized + Send {}
@@ -48,13 +208,65 @@ impl<T> InitStateLock<T> {
     }
 }
 
-impl<T> interface::Mutex for IRQSafeNullLock<T> {
+impl<T> interface::Mutex for SpinLock<T> {
     type Data = T;
 
     fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
+        self.lock.acquire();
+
         let data = unsafe { &mut *self.data.get() };
+        let ret = f(data);
+
+        self.lock.release();
+
+        ret
+    }
+}
+
+impl<T> interface::Mutex for IRQSafeSpinLock<T> {
+    type Data = T;
+
+    fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
+        exception::asynchronous::exec_with_irq_masked(|| {
+            self.lock.acquire();
+
+            let data = unsafe { &mut *self.data.get() };
+            let ret = f(data);
+
+            self.lock.release();
+
+            ret
+        })
+    }
+}
+
+impl<T> interface::ReadWriteEx for RwLock<T> {
+    type Data = T;
+
+    fn write<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
+        exception::asynchronous::exec_with_irq_masked(|| {
+            self.acquire_write();
+
+            let data = unsafe { &mut *self.data.get() };
+            let ret = f(data);
+
+            self.release_write();
+
+            ret
+        })
+    }
+
+    fn read<'a, R>(&'a self, f: impl FnOnce(&'a Self::Data) -> R) -> R {
+        exception::asynchronous::exec_with_irq_masked(|| {
+            self.acquire_read();
+
+            let data = unsafe { &*self.data.get() };
+            let ret = f(data);
+
+            self.release_read();
 
-        exception::asynchronous::exec_with_irq_masked(|| f(data))
+            ret
+        })
     }
 }
 
@@ -0,0 +1,141 @@
+use alloc::{string::String, vec};
+use core::time::Duration;
+
+use crate::{console, debug, exception::ExceptionContext, memory::{self, Address, Virtual}, sched, time};
+
+pub type SyscallArgs = [u64; 6];
+
+/// the only ABI version so far, new versions get their own table so old games keep working
+pub const ABI_VERSION_1: u16 = 1;
+
+/// the most bytes a single `write_console` call prints
+const MAX_WRITE_LEN: usize = 4096;
+
+#[derive(Copy, Clone)]
+pub enum SyscallError {
+    /// unknown ABI version or syscall number
+    NoSuchSyscall,
+    InvalidArgument,
+
+    /// a pointer argument does not point to mapped user memory
+    BadAddress,
+}
+
+/// what the dispatcher does after a handler returned
+pub enum Outcome {
+    Return(u64),
+    Exit(u64),
+    Sleep(Duration),
+    Yield,
+}
+
+type SyscallHandler = fn(&SyscallArgs) -> Result<Outcome, SyscallError>;
+
+struct SyscallDescriptor {
+    name: &'static str,
+    handler: SyscallHandler,
+}
+
+/// indexed by syscall number
+const SYSCALLS_V1: [SyscallDescriptor; 5] = [
+    SyscallDescriptor { name: "write_console", handler: sys_write_console },
+    SyscallDescriptor { name: "exit", handler: sys_exit },
+    SyscallDescriptor { name: "sleep", handler: sys_sleep },
+    SyscallDescriptor { name: "get_uptime", handler: sys_get_uptime },
+    SyscallDescriptor { name: "yield", handler: sys_yield },
+];
+
+impl SyscallError {
+    pub const fn code(self) -> i64 {
+        match self {
+            Self::NoSuchSyscall => -1,
+            Self::InvalidArgument => -2,
+            Self::BadAddress => -3,
+        }
+    }
+}
+
+fn syscall_table(abi_version: u16) -> Option<&'static [SyscallDescriptor]> {
+    match abi_version {
+        ABI_VERSION_1 => Some(&SYSCALLS_V1),
+        _ => None,
+    }
+}
+
+/// `write_console(buf: *const u8, len: usize) -> usize`, returns the number of bytes written
+fn sys_write_console(args: &SyscallArgs) -> Result<Outcome, SyscallError> {
+    let virt_addr = Address::<Virtual>::new(args[0] as usize);
+    let len = (args[1] as usize).min(MAX_WRITE_LEN);
+
+    let mut buf = vec![0u8; len];
+    memory::user_access::copy_from_user(&mut buf, virt_addr).map_err(|_| SyscallError::BadAddress)?;
+
+    console::console().write_fmt(format_args!("{}", String::from_utf8_lossy(&buf))).map_err(|_| SyscallError::InvalidArgument)?;
+
+    Ok(Outcome::Return(len as u64))
+}
+
+/// `exit(code: u64) -> !`
+fn sys_exit(args: &SyscallArgs) -> Result<Outcome, SyscallError> {
+    Ok(Outcome::Exit(args[0]))
+}
+
+/// `sleep(nanos: u64)`
+fn sys_sleep(args: &SyscallArgs) -> Result<Outcome, SyscallError> {
+    Ok(Outcome::Sleep(Duration::from_nanos(args[0])))
+}
+
+/// `get_uptime() -> u64`, in nanoseconds
+fn sys_get_uptime(_args: &SyscallArgs) -> Result<Outcome, SyscallError> {
+    Ok(Outcome::Return(time::time_manager().uptime().as_nanos() as u64))
+}
+
+/// `yield()`
+fn sys_yield(_args: &SyscallArgs) -> Result<Outcome, SyscallError> {
+    Ok(Outcome::Yield)
+}
+
+/// run the syscall requested by the user thread that `context` belongs to, returns the context
+/// to restore
+///
+/// a user thread issues `svc #<abi version>` with the syscall number in x8 and up to six
+/// arguments in x0 to x5. the result comes back in x0, errors as small negative numbers
+pub fn dispatch(context: &mut ExceptionContext) -> *mut ExceptionContext {
+    let abi_version = context.syscall_abi_version();
+    let number = context.syscall_number();
+    let args = context.syscall_args();
+
+    let descriptor = syscall_table(abi_version).and_then(|table| table.get(number as usize));
+
+    let outcome = match descriptor {
+        None => Err(SyscallError::NoSuchSyscall),
+        Some(descriptor) => {
+            debug!("syscall v{} {}({:#x?})", abi_version, descriptor.name, args);
+
+            (descriptor.handler)(&args)
+        }
+    };
+
+    match outcome {
+        Err(error) => {
+            context.set_syscall_result(error.code() as u64);
+            context
+        }
+        Ok(Outcome::Return(value)) => {
+            context.set_syscall_result(value);
+            context
+        }
+        Ok(Outcome::Exit(code)) => {
+            debug!("user thread exited with code {}", code);
+            sched::kill_current(context)
+        }
+        Ok(Outcome::Sleep(duration)) => {
+            context.set_syscall_result(0);
+            sched::sleep_current(context, duration)
+        }
+        Ok(Outcome::Yield) => {
+            context.set_syscall_result(0);
+            sched::reschedule(context)
+        }
+    }
+}
@@ -2,22 +2,103 @@
 #[path = "arch/aarch64/time.rs"]
 mod arch_time;
 
-use core::time::Duration;
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{sync::atomic::{AtomicBool, AtomicUsize, Ordering}, time::Duration};
 
-pub struct TimeManager;
+use crate::{bsp, cpu, driver, exception::{self, asynchronous::IRQNumber}, synchronization::{interface::Mutex, IRQSafeSpinLock}};
+
+pub type TimeoutId = usize;
+
+pub type TimeoutCallback = Box<dyn FnMut() + Send>;
+
+struct Timeout {
+    id: TimeoutId,
+    deadline: Duration,
+
+    /// `Some` for intervals, which are queued again after each run
+    period: Option<Duration>,
+
+    callback: TimeoutCallback,
+}
+
+/// the pending timeouts of a single core, sorted by deadline
+struct TimerQueue {
+    timeouts: Vec<Timeout>,
+
+    /// the timeout whose callback is running right now, it is not part of `timeouts` meanwhile
+    running: Option<TimeoutId>,
+    running_cancelled: bool,
+}
+
+/// every core has its own comparator, so every core also has its own queue. callbacks run in IRQ
+/// context on the core that set them up
+pub struct TimeManager {
+    queues: [IRQSafeSpinLock<TimerQueue>; bsp::cpu::NUM_CORES],
+}
 
 static TIME_MANAGER: TimeManager = TimeManager::new();
 
+static NEXT_TIMEOUT_ID: AtomicUsize = AtomicUsize::new(0);
+
 pub fn time_manager() -> &'static TimeManager {
     &TIME_MANAGER
 }
 
+impl TimerQueue {
+    const fn new() -> Self {
+        Self {
+            timeouts: Vec::new(),
+            running: None,
+            running_cancelled: false,
+        }
+    }
+
+    /// returns whether the timeout became the earliest one
+    fn insert(&mut self, timeout: Timeout) -> bool {
+        // timeouts with the same deadline run in the order they were added
+        let index = self.timeouts.partition_point(|t| t.deadline <= timeout.deadline);
+        self.timeouts.insert(index, timeout);
+
+        index == 0
+    }
+
+    fn pop_expired(&mut self, now: Duration) -> Option<Timeout> {
+        if self.timeouts.first()?.deadline > now {
+            return None;
+        }
+
+        Some(self.timeouts.remove(0))
+    }
+
+    fn next_deadline(&self) -> Option<Duration> {
+        self.timeouts.first().map(|t| t.deadline)
+    }
+
+    fn cancel(&mut self, id: TimeoutId) -> bool {
+        if self.running == Some(id) {
+            self.running_cancelled = true;
+            return true;
+        }
+
+        match self.timeouts.iter().position(|t| t.id == id) {
+            None => false,
+            Some(index) => {
+                self.timeouts.remove(index);
+                true
+            }
+        }
+    }
+}
+
 impl TimeManager {
+    pub const COMPATIBLE: &'static str = "ARM Architectural Timer";
+
     pub const fn new() -> Self {
-        Self
+        Self {
+            queues: [const { IRQSafeSpinLock::new(TimerQueue::new()) }; bsp::cpu::NUM_CORES],
+        }
     }
 
-    #[allow(unused)]
     pub fn resolution(&self) -> Duration {
         arch_time::resolution()
     }
@@ -30,4 +111,127 @@ impl TimeManager {
     pub fn spin_for(&self, duration: Duration) {
         arch_time::spin_for(duration)
     }
+
+    fn local_queue(&self) -> &IRQSafeSpinLock<TimerQueue> {
+        &self.queues[cpu::smp::core_id::<usize>()]
+    }
+
+    fn add_timeout(&self, duration: Duration, period: Option<Duration>, callback: TimeoutCallback) -> TimeoutId {
+        let id = NEXT_TIMEOUT_ID.fetch_add(1, Ordering::Relaxed);
+
+        self.local_queue().lock(|queue| {
+            let deadline = self.uptime() + duration;
+
+            if queue.insert(Timeout { id, deadline, period, callback }) {
+                arch_time::set_timeout_irq_at(deadline);
+            }
+        });
+
+        id
+    }
+
+    /// run `callback` once, after `duration` has passed
+    pub fn set_timeout(&self, duration: Duration, callback: impl FnOnce() + Send + 'static) -> TimeoutId {
+        let mut callback = Some(callback);
+
+        self.add_timeout(duration, None, Box::new(move || {
+            if let Some(callback) = callback.take() {
+                callback()
+            }
+        }))
+    }
+
+    /// run `callback` every `period`, until the interval is cancelled
+    pub fn set_interval(&self, period: Duration, callback: impl FnMut() + Send + 'static) -> TimeoutId {
+        assert!(period >= self.resolution(), "interval period below timer resolution");
+
+        self.add_timeout(period, Some(period), Box::new(callback))
+    }
+
+    /// cancel a timeout or interval, returns `false` if it does not exist (anymore)
+    pub fn cancel(&self, id: TimeoutId) -> bool {
+        self.queues.iter().any(|queue| queue.lock(|queue| queue.cancel(id)))
+    }
+
+    /// idle the current core with `wfi` until `duration` has passed
+    ///
+    /// IRQs must be unmasked, since the wake-up is delivered through the timer IRQ
+    pub fn sleep(&self, duration: Duration) {
+        let woken = Arc::new(AtomicBool::new(false));
+
+        let woken_by_timeout = woken.clone();
+        self.set_timeout(duration, move || woken_by_timeout.store(true, Ordering::Release));
+
+        while !woken.load(Ordering::Acquire) {
+            cpu::wfi();
+        }
+    }
+
+    /// run all expired callbacks of the current core and re-arm the comparator for the next one
+    fn handle_expired_timeouts(&self) {
+        let queue = self.local_queue();
+
+        loop {
+            let now = self.uptime();
+
+            let mut timeout = match queue.lock(|queue| {
+                let timeout = queue.pop_expired(now)?;
+                queue.running = Some(timeout.id);
+
+                Some(timeout)
+            }) {
+                None => break,
+                Some(timeout) => timeout,
+            };
+
+            // the queue is unlocked, so callbacks may add or cancel timeouts themselves
+            (timeout.callback)();
+
+            queue.lock(|queue| {
+                queue.running = None;
+
+                if core::mem::take(&mut queue.running_cancelled) {
+                    return;
+                }
+
+                if let Some(period) = timeout.period {
+                    // skip the periods that were missed instead of running them back to back
+                    timeout.deadline = (timeout.deadline + period).max(now + period);
+                    queue.insert(timeout);
+                }
+            });
+        }
+
+        queue.lock(|queue| match queue.next_deadline() {
+            None => arch_time::conclude_timeout_irq(),
+            Some(deadline) => arch_time::set_timeout_irq_at(deadline),
+        });
+    }
+}
+
+impl driver::interface::DeviceDriver for TimeManager {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    fn register_and_enable_irq_handler(&'static self, irq_number: &Self::IRQNumberType) -> Result<(), &'static str> {
+        use exception::asynchronous::{irq_manager, IRQHandlerDescriptor};
+
+        let descriptor = IRQHandlerDescriptor::new(*irq_number, Self::COMPATIBLE, self);
+
+        irq_manager().register_handler(descriptor)?;
+        irq_manager().enable(irq_number);
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQHandler for TimeManager {
+    fn handle(&self) -> Result<(), &'static str> {
+        self.handle_expired_timeouts();
+
+        Ok(())
+    }
 }
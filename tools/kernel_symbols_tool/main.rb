@@ -0,0 +1,69 @@
+#!/usr/bin/env ruby
+# frozen_string_literal: true
+
+require 'rubygems'
+require 'bundler/setup'
+require 'colorize'
+require 'elftools'
+
+# Builds the table read by `src/symbols.rs` and patches it into the `.kernel_symbols` section:
+#
+#   u64 number of symbols
+#   [u64 start, u32 size, u32 name offset, u32 name length, u32 reserved] sorted by start
+#   names, UTF-8, not terminated
+
+STT_FUNC = 2
+
+def demangle(names)
+  IO.popen(['rustfilt'], 'r+') do |io|
+    io.puts(names)
+    io.close_write
+    io.readlines(chomp: true)
+  end
+rescue Errno::ENOENT
+  puts 'Warning'.rjust(12).yellow.bold + ' rustfilt not found, keeping mangled names'
+  names
+end
+
+kernel_elf_path = ARGV[0]
+
+start = Time.now
+
+elf = ELFTools::ELFFile.new(File.open(kernel_elf_path))
+symtab = elf.section_by_name('.symtab')
+
+table_start = symtab.symbol_by_name('__kernel_symbols_start').header.st_value
+table_end = symtab.symbol_by_name('__kernel_symbols_end_exclusive').header.st_value
+
+functions = symtab.symbols.select do |symbol|
+  (symbol.header.st_info & 0xf) == STT_FUNC && symbol.header.st_size.positive?
+end
+functions = functions.uniq { |symbol| symbol.header.st_value }.sort_by { |symbol| symbol.header.st_value }
+
+names = demangle(functions.map(&:name))
+
+entries = ''.b
+strings = ''.b
+
+functions.zip(names).each do |symbol, name|
+  name = name.b
+  entries << [symbol.header.st_value, symbol.header.st_size, strings.bytesize, name.bytesize, 0].pack('Q<L<L<L<L<')
+  strings << name
+end
+
+table = [functions.size].pack('Q<') + entries + strings
+
+raise "symbol table needs #{table.bytesize} bytes, only #{table_end - table_start} reserved" if table.bytesize > table_end - table_start
+
+segment = elf.each_segments.find { |s| s.vma_in?(table_start) }
+table_offset_in_file = segment.vma_to_offset(table_start)
+
+print 'Patching'.rjust(12).green.bold
+puts " #{functions.size} symbols (#{table.bytesize} bytes) at ELF file offset 0x#{table_offset_in_file.to_s(16)}"
+
+File.binwrite(kernel_elf_path, table, table_offset_in_file)
+
+elapsed = Time.now - start
+
+print 'Finished'.rjust(12).green.bold
+puts " in #{elapsed.round(2)}s"